@@ -1,10 +1,14 @@
 // mod sensor;
+use pir_motion_sensor::actions::{Action, ActionConfig, Actions};
+use pir_motion_sensor::sensor::backend::GpioOutput;
+use pir_motion_sensor::sensor::bus::EventBus;
+use pir_motion_sensor::sensor::events::EventKind;
 use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
 use pir_motion_sensor::sensor::motion::MotionSensor;
 use std::time::Duration;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::{mpsc, Mutex};
-use tokio::time::sleep;
+use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
 
 use std::{sync::Arc, time::SystemTime};
@@ -65,11 +69,15 @@ async fn main() {
         ),
     ];
 
+    // every event of every sensor (valid detections, faults, etc.) is published to this bus
+    let bus = EventBus::new(100);
+
     // starting detector in the background
     let mut sensors = Vec::new();
 
     // bulding list of sensors to use it later
-    sensors_list.into_iter().for_each(|sensor| {
+    sensors_list.into_iter().for_each(|mut sensor| {
+        sensor.set_event_bus(bus.clone());
         sensors.push(Mutex::new(sensor));
     });
 
@@ -80,23 +88,58 @@ async fn main() {
 
     // helper function to run important threads (via tokio::spawn)
     // you don't have deal this is you don't want to - just leave it as it is
-    spawn_detection_threads(sensors, token.clone());
+    let detection_tasks = spawn_detection_threads(sensors, token.clone());
+
+    //
+    // actions: light connected (through a relay) to gpio PIN 21 is turned on for 30 seconds
+    // when motion is detected in the garage or at the main door, each next detection extends
+    // this time. Check actions::ActionConfig for more options.
+    //
+    let mut actions = Actions::new(vec![Action::new(
+        ActionConfig {
+            name: String::from("GarageLight"),
+            sensors: vec![String::from("Garage"), String::from("MainDoorSlow")],
+            pin_number: 21,
+            on_duration_milisecs: 30000,
+            retrigger_extends: true,
+            inverted: false, // true for relay modules which are turned on by the low state
+        },
+        Box::new(GpioOutput::open(21).expect("cannot open gpio PIN 21")),
+    )]);
+
+    // other events of all sensors (faults, etc.) as a stream (bus.sensor_stream() gives a
+    // single sensor), valid detections are taken from the detections channel
+    let mut events = bus
+        .stream()
+        .filter(|event| event.kind != EventKind::Detection);
+
+    let mut actions_update = tokio::time::interval(Duration::from_millis(100));
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
 
     //
     // main loop: here we put logic to handle valid detections, place your code here
     //
     loop {
-        if let Ok(detection_message) = detections_channel_receiver.try_recv() {
-            // valid detection received
-            // each "valid" detection contains the sensor name and time of detection as SystemTime
-            let (detection_name, detection_time) = detection_message;
-
-            println!("detection happened, sensor: {detection_name}, time: {detection_time:?} ");
-            //
-            // put your action here like alarm or turn on/off light
-            // to interact with rest GPIOs you can check rppal lib examples here: https://github.com/golemparts/rppal/tree/master/examples
-            //
+        tokio::select! {
+            Some((detection_name, detection_time)) = detections_channel_receiver.recv() => {
+                // valid detection received
+                // each "valid" detection contains the sensor name and time of detection as SystemTime
+                println!("detection happened, sensor: {detection_name}, time: {detection_time:?} ");
+
+                // actions bound to this sensor are turned on (put your own logic here as well)
+                actions.handle_detection(&detection_name);
+            }
+            Some(event) = events.next() => {
+                println!("event of sensor {}: {:?}", event.sensor_name, event.kind);
+            }
+            // actions are turned off once their time has passed
+            _ = actions_update.tick() => actions.update(),
+            _ = &mut ctrl_c => break,
         }
-        sleep(Duration::from_millis(1)).await;
     }
+
+    // waits until sensors are stopped, their gpio PINs are released as well
+    detection_tasks.shutdown().await;
 }
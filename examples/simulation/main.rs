@@ -0,0 +1,163 @@
+//
+// 60 virtual sensors (12 rooms, 5 sensors each) without any hardware: people walk through the
+// house, every sensor has some random noise and one of them breaks after 20 seconds. Valid
+// detections go through zones and rules, a summary is printed every 5 seconds. Ctrl-C to stop.
+//
+//   cargo run --example simulation
+//
+use pir_motion_sensor::rules::{RuleAction, RuleConfig, RuleEngine, ZoneConfig};
+use pir_motion_sensor::sensor::bus::EventBus;
+use pir_motion_sensor::sensor::config::{HealthConfig, SensorConfig};
+use pir_motion_sensor::sensor::detector::OverflowPolicy;
+use pir_motion_sensor::sensor::events::EventKind;
+use pir_motion_sensor::sensor::manager::SensorManager;
+use pir_motion_sensor::sensor::simulation::{Noise, Scenario, Simulation, StuckHigh, Walk};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+const ROOMS: usize = 12;
+const SENSORS_PER_ROOM: usize = 5;
+
+fn sensor_name(room: usize, sensor: usize) -> String {
+    format!("Room{room:02}-{sensor}")
+}
+
+#[tokio::main]
+async fn main() {
+    //
+    // scenario: one person walks from the first room to the last one every 30 seconds (in front
+    // of the first sensor of each room), another one walks back through the second sensors.
+    // It's the same as [simulation] section of pir-motiond config file.
+    //
+    let forward: Vec<_> = (1..=ROOMS).map(|room| sensor_name(room, 1)).collect();
+    let backward: Vec<_> = (1..=ROOMS).rev().map(|room| sensor_name(room, 2)).collect();
+
+    let scenario = Scenario {
+        seed: 2024,
+        walks: vec![
+            Walk {
+                sensors: forward,
+                start_milisecs: 1000,
+                dwell_milisecs: 1500,
+                gap_milisecs: 500,
+                every_milisecs: Some(30_000),
+            },
+            Walk {
+                sensors: backward,
+                start_milisecs: 5000,
+                dwell_milisecs: 1500,
+                gap_milisecs: 500,
+                every_milisecs: Some(30_000),
+            },
+        ],
+        // short random triggers on every sensor, they should not give valid detections
+        noise: vec![Noise {
+            sensors: Vec::new(),
+            rate_per_minute: 6.0,
+            pulse_milisecs: 150,
+        }],
+        stuck_high: vec![StuckHigh {
+            sensor: sensor_name(7, 5),
+            from_milisecs: 20_000,
+            to_milisecs: None,
+        }],
+    };
+    let simulation = Simulation::new(scenario);
+
+    let bus = EventBus::new(1024);
+    let mut events = bus.stream();
+
+    let mut manager = SensorManager::new();
+    manager.set_event_bus(bus.clone());
+
+    for room in 1..=ROOMS {
+        for sensor in 1..=SENSORS_PER_ROOM {
+            let config = SensorConfig {
+                name: sensor_name(room, sensor),
+                // not used by simulated sensors
+                pin_number: 0,
+                refresh_rate_milisecs: 100,
+                motion_time_period_milisecs: 500,
+                minimal_triggering_number: 4,
+                // a short noise pulse doesn't leave many readings waiting for processing
+                overflow_policy: OverflowPolicy::Coalesce,
+                health: HealthConfig {
+                    stuck_high_milisecs: Some(5000),
+                    no_activity_milisecs: None,
+                    quarantine: true,
+                },
+                ..Default::default()
+            };
+
+            let input = simulation.input(&config.name);
+            manager
+                .add_sensor_with_input(config, Box::new(input))
+                .expect("cannot add sensor");
+        }
+    }
+
+    // a zone for each room, the rule fires when somebody goes from the first room to the last one
+    let zones = (1..=ROOMS)
+        .map(|room| ZoneConfig {
+            name: format!("Room{room:02}"),
+            sensors: (1..=SENSORS_PER_ROOM)
+                .map(|sensor| sensor_name(room, sensor))
+                .collect(),
+        })
+        .collect();
+    let rules = vec![RuleConfig {
+        name: String::from("WholeHouse"),
+        when: vec![String::from("Room01"), format!("Room{ROOMS:02}")],
+        within_milisecs: 30_000,
+        schedule: None,
+        rate_limit_milisecs: 10_000,
+        dry_run: true,
+        then: vec![RuleAction::Webhook {
+            url: String::from("http://127.0.0.1:8080/alarm"),
+        }],
+    }];
+    let mut rules = RuleEngine::new(zones, rules).expect("invalid rules");
+
+    println!("{} virtual sensors started", manager.len());
+
+    let mut detections: BTreeMap<String, u64> = BTreeMap::new();
+    let mut faults = 0;
+    let mut firings = 0;
+
+    let mut summary = tokio::time::interval(Duration::from_secs(5));
+    summary.tick().await;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        tokio::select! {
+            Some(event) = events.next() => match event.kind {
+                EventKind::Detection => {
+                    *detections.entry(event.sensor_name.clone()).or_default() += 1;
+                    firings += rules.handle_detection(&event.sensor_name, event.time).len();
+                }
+                EventKind::Fault(fault) => {
+                    faults += 1;
+                    println!("fault of {}: {}", event.sensor_name, fault.as_str());
+                }
+                _ => {}
+            },
+            _ = summary.tick() => {
+                println!(
+                    "valid detections: {} (from {} sensors), faults: {faults}, rule firings: {firings}",
+                    detections.values().sum::<u64>(),
+                    detections.len(),
+                );
+            }
+            _ = &mut ctrl_c => break,
+        }
+    }
+
+    manager.stop().await;
+
+    for (sensor, count) in detections.iter() {
+        println!("{sensor}: {count}");
+    }
+}
@@ -0,0 +1,199 @@
+#![no_main]
+
+//
+// Detection core driven by random operations on a made up clock, the same invariants as
+// tests/properties.rs, but also for configs which are never accepted by SensorConfig::validate()
+// (zero refresh rate or buffer capacity, minimal triggering number below 1, etc.)
+//
+//   cargo +nightly fuzz run detection_core
+//
+use arbitrary::Arbitrary;
+use core::time::Duration;
+use libfuzzer_sys::fuzz_target;
+use pir_motion_sensor::sensor::detector::{
+    CoreEvent, DetectionCore, DetectorConfig, OverflowPolicy,
+};
+
+#[derive(Arbitrary, Debug)]
+enum Policy {
+    DropNewest,
+    DropOldest,
+    Coalesce,
+    Block,
+}
+
+impl From<Policy> for OverflowPolicy {
+    fn from(policy: Policy) -> Self {
+        match policy {
+            Policy::DropNewest => OverflowPolicy::DropNewest,
+            Policy::DropOldest => OverflowPolicy::DropOldest,
+            Policy::Coalesce => OverflowPolicy::Coalesce,
+            Policy::Block => OverflowPolicy::Block,
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    // clock moves forward, then sensor output is read and pending readings are processed
+    Update { after_milisecs: u16, high: bool },
+    Sample { after_milisecs: u16, high: bool },
+    Poll { after_milisecs: u16 },
+    Flush,
+    ClearPending,
+    SetMinimalTriggeringNumber(i16),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    refresh_rate_milisecs: u16,
+    motion_time_period_milisecs: u16,
+    minimal_triggering_number: i16,
+    warmup_milisecs: u16,
+    buffer_capacity: u8,
+    overflow_policy: Policy,
+    operations: Vec<Operation>,
+}
+
+#[derive(Default)]
+struct Checker {
+    // time of reading of every trigger counted towards valid detections
+    triggers: Vec<Duration>,
+    // triggers processed before the last valid detection
+    detected_triggers: usize,
+    last_processed: Option<Duration>,
+    ready: bool,
+    high_readings: u64,
+    refused_readings: u64,
+    cleared_readings: u64,
+}
+
+impl Checker {
+    fn sample(&mut self, core: &mut DetectionCore, now: Duration, high: bool) {
+        if high {
+            self.high_readings += 1;
+        }
+        let dropped = core.dropped_readings();
+        if !core.sample(now, high) && core.dropped_readings() == dropped {
+            self.refused_readings += 1;
+        }
+        assert!(core.pending() <= core.capacity());
+    }
+
+    fn poll(
+        &mut self,
+        core: &mut DetectionCore,
+        poll: impl FnOnce(&mut DetectionCore) -> Option<CoreEvent>,
+    ) {
+        let config = *core.config();
+        let reading = core.oldest_pending();
+        let processed = core.pre_detections() + core.warmup_triggers();
+        let pre_detections = core.pre_detections();
+        let last_check = core.last_check();
+
+        let event = poll(core);
+
+        // at most one reading per refresh rate - readings discarded at the end of warm-up are
+        // not processed
+        if core.last_check() != last_check
+            && core.pre_detections() + core.warmup_triggers() != processed
+        {
+            if let Some(last) = self.last_processed {
+                let since_last = core.last_check().saturating_sub(last);
+                assert!(since_last.as_millis() > config.refresh_rate.as_millis());
+            }
+            self.last_processed = Some(core.last_check());
+        }
+
+        if core.pre_detections() != pre_detections {
+            let reading = reading.unwrap();
+            // readings taken during warm-up are never counted
+            assert!(reading >= config.warmup);
+            // readings are processed in order
+            assert!(self.triggers.last().is_none_or(|last| *last <= reading));
+            self.triggers.push(reading);
+        }
+
+        match event {
+            Some(CoreEvent::Detection) => {
+                // enough triggers within motion time period
+                let min = config.minimal_triggering_number.max(1) as usize;
+                assert!(self.triggers.len() - self.detected_triggers >= min);
+                let counted = &self.triggers[self.triggers.len() - min..];
+                assert!(counted[min - 1] - counted[0] <= config.motion_time_period);
+                // never during warm-up - flush() may process readings with time of the core
+                // before the end of warm-up, but only after Ready
+                assert!(config.warmup.is_zero() || self.ready);
+                self.detected_triggers = self.triggers.len();
+            }
+            Some(CoreEvent::Ready) => {
+                assert!(!self.ready && !config.warmup.is_zero());
+                self.ready = true;
+            }
+            None => {}
+        }
+    }
+
+    fn check_readings(&self, core: &DetectionCore) {
+        assert_eq!(
+            self.high_readings,
+            core.pre_detections()
+                + core.warmup_triggers()
+                + core.pending() as u64
+                + core.dropped_readings()
+                + core.coalesced_readings()
+                + self.refused_readings
+                + self.cleared_readings
+        );
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let mut core = DetectionCore::new(DetectorConfig {
+        refresh_rate: Duration::from_millis(input.refresh_rate_milisecs.into()),
+        motion_time_period: Duration::from_millis(input.motion_time_period_milisecs.into()),
+        minimal_triggering_number: input.minimal_triggering_number,
+        warmup: Duration::from_millis(input.warmup_milisecs.into()),
+        buffer_capacity: input.buffer_capacity,
+        overflow_policy: input.overflow_policy.into(),
+    });
+    let mut checker = Checker::default();
+    let mut now = Duration::ZERO;
+
+    for operation in input.operations {
+        match operation {
+            Operation::Update {
+                after_milisecs,
+                high,
+            } => {
+                now += Duration::from_millis(after_milisecs.into());
+                checker.sample(&mut core, now, high);
+                checker.poll(&mut core, |core| core.poll(now));
+            }
+            Operation::Sample {
+                after_milisecs,
+                high,
+            } => {
+                now += Duration::from_millis(after_milisecs.into());
+                checker.sample(&mut core, now, high);
+            }
+            Operation::Poll { after_milisecs } => {
+                now += Duration::from_millis(after_milisecs.into());
+                checker.poll(&mut core, |core| core.poll(now));
+            }
+            Operation::Flush => {
+                checker.poll(&mut core, |core| core.flush());
+                // flush() moves time of the core forward
+                now = now.max(core.last_check());
+            }
+            Operation::ClearPending => {
+                checker.cleared_readings += core.pending() as u64;
+                core.clear_pending();
+            }
+            Operation::SetMinimalTriggeringNumber(minimal_triggering_number) => {
+                core.set_minimal_triggering_number(minimal_triggering_number);
+            }
+        }
+        checker.check_readings(&core);
+    }
+});
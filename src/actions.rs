@@ -0,0 +1,238 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+
+use crate::sensor::backend::OutputBackend;
+use crate::sensor::clock::{Clock, TokioClock};
+
+// how often Actions::run() checks if outputs should be turned off
+const UPDATE_INTERVAL: Duration = Duration::from_millis(10);
+
+//
+// Output (relay, light, buzzer) turned on by valid detections of given sensors, for instance:
+//
+//   [[actions]]
+//   name = "GarageLight"
+//   sensors = ["Garage", "MainDoorSlow"]
+//   pin_number = 21
+//   on_duration_milisecs = 30000
+//
+#[derive(Deserialize, Debug, Clone)]
+pub struct ActionConfig {
+    pub name: String,
+    // names of sensors whose valid detections turn the output on
+    pub sensors: Vec<String>,
+    // output pin, used when action is created from config file
+    pub pin_number: u8,
+    // how long output stays on after detection
+    pub on_duration_milisecs: u64,
+    // true - every detection while output is on extends on-time by "on_duration_milisecs",
+    // false - output is turned off "on_duration_milisecs" after the first detection
+    #[serde(default = "default_retrigger_extends")]
+    pub retrigger_extends: bool,
+    // true - output is low when action is on (active low relay modules)
+    #[serde(default)]
+    pub inverted: bool,
+}
+
+fn default_retrigger_extends() -> bool {
+    true
+}
+
+// manual control of action output, detections are still tracked while it's not Auto
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Override {
+    // output follows detections
+    #[default]
+    Auto,
+    ForceOn,
+    ForceOff,
+}
+
+#[derive(Debug)]
+pub struct Action {
+    pub config: ActionConfig,
+    output: Box<dyn OutputBackend>,
+    // None - turned off by detections
+    on_until: Option<Instant>,
+    mode: Override,
+    is_on: bool,
+}
+
+impl Action {
+    // output is turned off at creation
+    pub fn new(config: ActionConfig, output: Box<dyn OutputBackend>) -> Self {
+        let mut action = Self {
+            config,
+            output,
+            on_until: None,
+            mode: Override::Auto,
+            is_on: true,
+        };
+        action.apply(false);
+        action
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+
+    pub fn mode(&self) -> Override {
+        self.mode
+    }
+
+    // true when detection of this sensor is bound to the action
+    pub fn handle_detection(&mut self, sensor_name: &str, now: Instant) -> bool {
+        if !self.config.sensors.iter().any(|s| s == sensor_name) {
+            return false;
+        }
+
+        self.trigger(now);
+        true
+    }
+
+    // turns the output on as a detection of its sensor would do
+    pub fn trigger(&mut self, now: Instant) {
+        let on_duration = Duration::from_millis(self.config.on_duration_milisecs);
+
+        match self.on_until {
+            Some(on_until) if on_until > now && !self.config.retrigger_extends => {}
+            _ => self.on_until = Some(now + on_duration),
+        }
+
+        self.update(now);
+    }
+
+    pub fn set_override(&mut self, mode: Override, now: Instant) {
+        if mode != self.mode {
+            info!("action {}: override {:?}", self.config.name, mode);
+        }
+        self.mode = mode;
+        self.update(now);
+    }
+
+    // sets output according to override and detections, turns it off when on-time has passed
+    pub fn update(&mut self, now: Instant) {
+        if self.on_until.is_some_and(|on_until| on_until <= now) {
+            self.on_until = None;
+        }
+
+        let on = match self.mode {
+            Override::Auto => self.on_until.is_some(),
+            Override::ForceOn => true,
+            Override::ForceOff => false,
+        };
+
+        self.apply(on);
+    }
+
+    fn apply(&mut self, on: bool) {
+        if on == self.is_on {
+            return;
+        }
+
+        info!(
+            "action {}: {}",
+            self.config.name,
+            if on { "on" } else { "off" }
+        );
+        self.is_on = on;
+        self.output.set_high(on != self.config.inverted);
+    }
+}
+
+//
+// Set of actions driven by valid detections. Either call handle_detection()/update() from your
+// own loop, or let run() do it with detections from a channel.
+//
+#[derive(Debug)]
+pub struct Actions {
+    actions: Vec<Action>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Actions {
+    pub fn new(actions: Vec<Action>) -> Self {
+        Self {
+            actions,
+            clock: Arc::new(TokioClock::new()),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.actions.iter().find(|a| a.config.name == name)
+    }
+
+    pub fn handle_detection(&mut self, sensor_name: &str) {
+        let now = self.clock.now();
+        for action in self.actions.iter_mut() {
+            action.handle_detection(sensor_name, now);
+        }
+    }
+
+    // false - there is no action with this name
+    pub fn trigger(&mut self, name: &str) -> bool {
+        let now = self.clock.now();
+        match self.actions.iter_mut().find(|a| a.config.name == name) {
+            Some(action) => {
+                action.trigger(now);
+                true
+            }
+            None => {
+                warn!("trigger of unknown action {name}");
+                false
+            }
+        }
+    }
+
+    // false - there is no action with this name
+    pub fn set_override(&mut self, name: &str, mode: Override) -> bool {
+        let now = self.clock.now();
+        match self.actions.iter_mut().find(|a| a.config.name == name) {
+            Some(action) => {
+                action.set_override(mode, now);
+                true
+            }
+            None => {
+                warn!("override of unknown action {name}");
+                false
+            }
+        }
+    }
+
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        for action in self.actions.iter_mut() {
+            action.update(now);
+        }
+    }
+}
+
+//
+// drives actions with valid detections from the channel until it's closed or "stop" is
+// cancelled. Actions stay available for overrides through the mutex.
+//
+pub async fn run(
+    actions: Arc<Mutex<Actions>>,
+    mut detections: Receiver<(String, SystemTime)>,
+    stop: Arc<CancellationToken>,
+) {
+    loop {
+        tokio::select! {
+            _ = stop.cancelled() => break,
+            detection = detections.recv() => match detection {
+                Some((sensor_name, _)) => actions.lock().await.handle_detection(&sensor_name),
+                None => break,
+            },
+            _ = tokio::time::sleep(UPDATE_INTERVAL) => actions.lock().await.update(),
+        }
+    }
+}
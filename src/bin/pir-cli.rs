@@ -0,0 +1,200 @@
+//
+// pir-cli: diagnostic tools for sensors connected to GPIO pins
+//
+// Example (while adjusting potentiometers of HC-SR501 connected to pins 16 and 25):
+//   pir-cli monitor --pin 16 --pin 25 --refresh-rate 100 --motion-period 500 --triggering-number 5
+//
+// Output:
+//       12.305s  pin16  HIGH
+//       12.406s  pin16  counter 1/5
+//       ...
+//       12.807s  pin16  VALID DETECTION
+//
+use clap::{Parser, Subcommand};
+use pir_motion_sensor::sensor::backend::GpioInput;
+use pir_motion_sensor::sensor::config::{ActiveLevel, Bias, SensorConfig};
+use pir_motion_sensor::sensor::motion::MotionSensor;
+use std::process::ExitCode;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "PIR motion sensors diagnostic tools")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prints live timeline of sensor output, pre-detection counters and valid detections
+    Monitor {
+        #[arg(
+            long = "pin",
+            required = true,
+            help = "GPIO pin number (BCM), may be repeated"
+        )]
+        pins: Vec<u8>,
+        #[arg(long, default_value_t = 100)]
+        refresh_rate: u64,
+        #[arg(long, default_value_t = 500)]
+        motion_period: u64,
+        #[arg(long, default_value_t = 5)]
+        triggering_number: i16,
+        #[arg(
+            long,
+            default_value = "none",
+            value_parser = ["none", "pull-up", "pull-down"],
+            help = "internal resistor of pins"
+        )]
+        bias: String,
+        #[arg(long, help = "sensor output is low during motion")]
+        active_low: bool,
+        #[arg(
+            long,
+            help = "print every sample read from pins, by default only changes of level are printed"
+        )]
+        all_samples: bool,
+    },
+}
+
+// state of single monitored sensor
+struct Monitored {
+    sensor: MotionSensor,
+    level: Option<bool>,
+    trigger_count: i16,
+    last_check_time: Instant,
+}
+
+fn print_event(started: Instant, sensor_name: &str, event: &str) {
+    println!(
+        "{:>12.3}s  {sensor_name}  {event}",
+        started.elapsed().as_secs_f64()
+    );
+}
+
+async fn monitor(config: SensorConfig, pins: Vec<u8>, all_samples: bool) -> ExitCode {
+    if let Err(e) = config.validate() {
+        eprintln!("invalid settings: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    let (detections_in, mut detections_out) = mpsc::channel::<(String, SystemTime)>(100);
+
+    let mut sensors = Vec::new();
+    for pin in pins {
+        let input = match GpioInput::open(pin) {
+            Ok(i) => i,
+            Err(e) => {
+                eprintln!("cannot open pin {pin}: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let sensor_config = SensorConfig {
+            name: format!("pin{pin}"),
+            pin_number: pin,
+            ..config.clone()
+        };
+
+        let sensor =
+            MotionSensor::with_input(sensor_config, detections_in.clone(), Box::new(input));
+        let last_check_time = sensor.now();
+        sensors.push(Monitored {
+            sensor,
+            level: None,
+            trigger_count: 0,
+            last_check_time,
+        });
+    }
+
+    println!(
+        "monitoring {} sensor(s): refresh rate {} ms, motion period {} ms, triggering number {}, Ctrl-C to stop\n",
+        sensors.len(),
+        config.refresh_rate_milisecs,
+        config.motion_time_period_milisecs,
+        config.minimal_triggering_number
+    );
+
+    let started = Instant::now();
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        for monitored in sensors.iter_mut() {
+            let name = monitored.sensor.config.name.clone();
+
+            let level = monitored.sensor.reading_from_sensor().await;
+            if all_samples || monitored.level != Some(level) {
+                print_event(started, &name, if level { "HIGH" } else { "low" });
+                monitored.level = Some(level);
+            }
+
+            let (trigger_count, last_check_time) = monitored
+                .sensor
+                .process_detections(monitored.trigger_count, monitored.last_check_time)
+                .await;
+
+            // counter is reset to 0 after valid detection, that's reported below
+            if trigger_count != monitored.trigger_count && trigger_count > 0 {
+                print_event(
+                    started,
+                    &name,
+                    &format!(
+                        "counter {trigger_count}/{}",
+                        monitored.sensor.config.minimal_triggering_number
+                    ),
+                );
+            }
+
+            monitored.trigger_count = trigger_count;
+            monitored.last_check_time = last_check_time;
+        }
+
+        while let Ok((sensor_name, _)) = detections_out.try_recv() {
+            print_event(started, &sensor_name, "VALID DETECTION");
+        }
+
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = tokio::time::sleep(Duration::from_micros(100)) => {}
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Monitor {
+            pins,
+            refresh_rate,
+            motion_period,
+            triggering_number,
+            bias,
+            active_low,
+            all_samples,
+        } => {
+            let config = SensorConfig {
+                refresh_rate_milisecs: refresh_rate,
+                motion_time_period_milisecs: motion_period,
+                minimal_triggering_number: triggering_number,
+                bias: match bias.as_str() {
+                    "pull-up" => Bias::PullUp,
+                    "pull-down" => Bias::PullDown,
+                    _ => Bias::None,
+                },
+                active_level: match active_low {
+                    true => ActiveLevel::Low,
+                    false => ActiveLevel::High,
+                },
+                ..Default::default()
+            };
+
+            monitor(config, pins, all_samples).await
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//
+// pir-motiond: runs sensors from a config file (see pir_motion_sensor::daemon for its format)
+//
+// Signals:
+//   SIGTERM, SIGINT - graceful stop
+//   SIGHUP          - config file is loaded again and sensors are restarted with new settings
+//
+// Works with systemd units of Type=notify (READY=1, RELOADING=1, STOPPING=1) and WatchdogSec.
+//
+use clap::Parser;
+use log::{error, info, warn};
+use pir_motion_sensor::actions::{self, Action, Actions};
+use pir_motion_sensor::daemon::{
+    DaemonConfig, detection_json, event_json, firing_json, pulse_action_name, sd_notify,
+    sd_watchdog_interval,
+};
+use pir_motion_sensor::rules::{RuleAction, RuleEngine};
+use pir_motion_sensor::sensor::backend::{GpioOutput, OutputBackend, VirtualOutput};
+use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind};
+use pir_motion_sensor::sensor::manager::SensorManager;
+use pir_motion_sensor::sensor::simulation::Simulation;
+use pir_motion_sensor::sinks::jsonl::JsonLinesSink;
+use pir_motion_sensor::sinks::mqtt::MqttSink;
+use pir_motion_sensor::sinks::webhook::WebhookSink;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, timeout_at};
+use tokio_util::sync::CancellationToken;
+
+// time for sinks to deliver what sensors sent before stop
+const SINKS_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Parser, Debug)]
+#[command(version, about = "PIR motion sensors daemon")]
+struct Args {
+    #[arg(
+        long,
+        short,
+        default_value = "/etc/pir-motiond.toml",
+        help = "config file (TOML)"
+    )]
+    config: PathBuf,
+}
+
+// sensors and sinks started from a single version of config file
+struct Running {
+    token: Arc<CancellationToken>,
+    sensors: SensorManager,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+fn start(config: &DaemonConfig) -> Result<Running, String> {
+    let token = Arc::new(CancellationToken::new());
+    let (detections_in, mut detections_out) = mpsc::channel::<(String, SystemTime)>(100);
+    // faults, etc. - valid detections are taken from detections channel
+    let (events_in, mut events_out) = mpsc::channel::<DetectionEvent>(100);
+
+    // sensors are stopped before sinks, see stop()
+    let mut manager = SensorManager::with_cancellation_token(Arc::new(token.child_token()));
+    manager.set_detection_channel(detections_in);
+    manager.set_event_channel(events_in);
+    let simulation = config.simulation.clone().map(Simulation::new);
+    for sensor_config in config.sensors.iter() {
+        let added = match simulation.as_ref() {
+            Some(simulation) => manager.add_sensor_with_input(
+                sensor_config.clone(),
+                Box::new(simulation.input(&sensor_config.name)),
+            ),
+            None => manager.add_sensor(sensor_config.clone()),
+        };
+
+        if let Err(e) = added {
+            // already started sensors are stopped
+            token.cancel();
+            return Err(e.to_string());
+        }
+    }
+
+    let mut tasks = Vec::new();
+
+    let webhook = match config.sinks.webhook.clone() {
+        Some(webhook_config) => {
+            let sink = WebhookSink::new(webhook_config).map_err(|e| format!("webhook: {e}"))?;
+            let (webhook_in, webhook_out) = mpsc::channel(100);
+            tasks.push(tokio::spawn(sink.run(webhook_out, token.clone())));
+            Some(webhook_in)
+        }
+        None => None,
+    };
+
+    let jsonl = match config.sinks.jsonl.clone() {
+        Some(jsonl_config) => {
+            let sink = JsonLinesSink::new(jsonl_config).map_err(|e| format!("jsonl: {e}"))?;
+            let (jsonl_in, jsonl_out) = mpsc::channel(100);
+            tasks.push(tokio::spawn(sink.run(jsonl_out, token.clone())));
+            Some(jsonl_in)
+        }
+        None => None,
+    };
+
+    let mqtt = match config.sinks.mqtt.clone() {
+        Some(mqtt_config) => {
+            let sink = MqttSink::new(mqtt_config).map_err(|e| format!("mqtt: {e}"))?;
+            let (mqtt_in, mqtt_out) = mpsc::channel(100);
+            tasks.push(tokio::spawn(sink.run(mqtt_out, token.clone())));
+            Some(mqtt_in)
+        }
+        None => None,
+    };
+
+    let rules_webhook = match config.rules_webhook() {
+        Some(webhook_config) => {
+            let sink =
+                WebhookSink::new(webhook_config).map_err(|e| format!("rules webhook: {e}"))?;
+            let (webhook_in, webhook_out) = mpsc::channel(100);
+            tasks.push(tokio::spawn(sink.run(webhook_out, token.clone())));
+            Some(webhook_in)
+        }
+        None => None,
+    };
+
+    let mut rules = match config.rules.is_empty() {
+        true => None,
+        false => Some(RuleEngine::new(config.zones.clone(), config.rules.clone())?),
+    };
+
+    let action_configs: Vec<_> = config
+        .actions
+        .iter()
+        .cloned()
+        .chain(config.pulse_actions())
+        .collect();
+
+    let actions = if action_configs.is_empty() {
+        None
+    } else {
+        let mut list = Vec::new();
+        for action_config in action_configs {
+            let output: Box<dyn OutputBackend> = match simulation {
+                Some(_) => Box::new(VirtualOutput::new()),
+                None => Box::new(
+                    GpioOutput::open(action_config.pin_number)
+                        .map_err(|e| format!("action {}: {e}", action_config.name))?,
+                ),
+            };
+            list.push(Action::new(action_config, output));
+        }
+
+        let actions = Arc::new(Mutex::new(Actions::new(list)));
+        let (actions_in, actions_out) = mpsc::channel(100);
+        tasks.push(tokio::spawn(actions::run(
+            actions.clone(),
+            actions_out,
+            token.clone(),
+        )));
+        Some((actions, actions_in))
+    };
+
+    let stdout_json = config.sinks.stdout_json;
+    let dispatcher_token = token.clone();
+    tasks.push(tokio::spawn(async move {
+        let mut detections_open = true;
+        let mut events_open = true;
+
+        // until sensors are stopped and everything they sent is dispatched
+        while detections_open || events_open {
+            let (sensor_name, detection_time) = tokio::select! {
+                _ = dispatcher_token.cancelled() => break,
+                detection = detections_out.recv(), if detections_open => match detection {
+                    Some(d) => d,
+                    None => {
+                        detections_open = false;
+                        continue;
+                    }
+                },
+                event = events_out.recv(), if events_open => {
+                    let Some(event) = event else {
+                        events_open = false;
+                        continue;
+                    };
+
+                    if event.kind != EventKind::Detection && stdout_json {
+                        println!("{}", event_json(&event));
+                    }
+
+                    // all events, valid detections as well
+                    if let Some(mqtt) = mqtt.as_ref()
+                        && mqtt.try_send(event.clone()).is_err()
+                    {
+                        warn!("mqtt sink is not keeping up, event dropped");
+                    }
+                    if let Some(jsonl) = jsonl.as_ref()
+                        && jsonl.try_send(event).is_err()
+                    {
+                        warn!("jsonl sink is not keeping up, event dropped");
+                    }
+                    continue;
+                }
+            };
+
+            if stdout_json {
+                println!("{}", detection_json(&sensor_name, detection_time));
+            }
+
+            if let Some((_, actions_in)) = actions.as_ref()
+                && actions_in
+                    .try_send((sensor_name.clone(), detection_time))
+                    .is_err()
+            {
+                warn!("actions are not keeping up, detection of {sensor_name} dropped");
+            }
+
+            if let Some(webhook) = webhook.as_ref()
+                && webhook
+                    .try_send((sensor_name.clone(), detection_time))
+                    .is_err()
+            {
+                warn!("webhook sink is not keeping up, detection of {sensor_name} dropped");
+            }
+
+            let firings = match rules.as_mut() {
+                Some(rules) => rules.handle_detection(&sensor_name, detection_time),
+                None => Vec::new(),
+            };
+
+            for firing in firings {
+                if stdout_json {
+                    println!("{}", firing_json(&firing));
+                }
+
+                if firing.dry_run {
+                    continue;
+                }
+
+                for action in firing.actions.iter() {
+                    match action {
+                        // all urls of the rule are sent by a single request to the sink
+                        RuleAction::Webhook { .. } => {}
+                        RuleAction::Pulse { pin_number, .. } => {
+                            if let Some((actions, _)) = actions.as_ref() {
+                                let name = pulse_action_name(&firing.rule, *pin_number);
+                                actions.lock().await.trigger(&name);
+                            }
+                        }
+                        RuleAction::Action { name } => {
+                            if let Some((actions, _)) = actions.as_ref() {
+                                actions.lock().await.trigger(name);
+                            }
+                        }
+                    }
+                }
+
+                let has_webhook = firing
+                    .actions
+                    .iter()
+                    .any(|a| matches!(a, RuleAction::Webhook { .. }));
+
+                if has_webhook
+                    && let Some(webhook) = rules_webhook.as_ref()
+                    && webhook
+                        .try_send((firing.rule.clone(), firing.time))
+                        .is_err()
+                {
+                    warn!(
+                        "rules webhook is not keeping up, rule {} dropped",
+                        firing.rule
+                    );
+                }
+            }
+        }
+    }));
+
+    info!("started {} sensor(s)", manager.len());
+
+    Ok(Running {
+        token,
+        sensors: manager,
+        tasks,
+    })
+}
+
+//
+// sensors are stopped first (their pins are released), so their last events still reach sinks.
+// Sinks finish when channels are closed - the token stops them if they take too long.
+//
+async fn stop(running: Running) {
+    let Running {
+        token,
+        mut sensors,
+        tasks,
+    } = running;
+
+    sensors.stop().await;
+    drop(sensors);
+
+    let deadline = Instant::now() + SINKS_STOP_TIMEOUT;
+    for mut task in tasks {
+        if timeout_at(deadline, &mut task).await.is_err() {
+            token.cancel();
+            let _ = task.await;
+        }
+    }
+
+    token.cancel();
+}
+
+fn notify(state: &str) {
+    if let Err(e) = sd_notify(state) {
+        warn!("cannot notify systemd ({state}): {e}");
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args = Args::parse();
+
+    let config = match DaemonConfig::load(&args.config) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("cannot load config {}: {e}", args.config.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut running = match start(&config) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("cannot start: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (Ok(mut sigterm), Ok(mut sigint), Ok(mut sighup)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::hangup()),
+    ) else {
+        error!("cannot install signal handlers");
+        return ExitCode::FAILURE;
+    };
+
+    notify("READY=1");
+
+    // None - systemd watchdog is not enabled for this service
+    let mut watchdog = sd_watchdog_interval().map(tokio::time::interval);
+
+    loop {
+        tokio::select! {
+            _ = sigterm.recv() => break,
+            _ = sigint.recv() => break,
+            _ = async { watchdog.as_mut().unwrap().tick().await }, if watchdog.is_some() => {
+                notify("WATCHDOG=1");
+            }
+            _ = sighup.recv() => {
+                info!("reloading config {}", args.config.display());
+
+                let config = match DaemonConfig::load(&args.config) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        // keep running with the previous config
+                        error!("cannot load config, reload skipped: {e}");
+                        continue;
+                    }
+                };
+
+                notify("RELOADING=1");
+                stop(running).await;
+
+                running = match start(&config) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        error!("cannot start with new config: {e}");
+                        notify("STOPPING=1");
+                        return ExitCode::FAILURE;
+                    }
+                };
+
+                notify("READY=1");
+            }
+        }
+    }
+
+    info!("stopping");
+    notify("STOPPING=1");
+    stop(running).await;
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,163 @@
+//
+// pir-tune: finds sensor settings for a recorded trace
+//
+// Trace is recorded with MotionSensor::record_trace() (see sensor::trace), labels file contains
+// intervals of real motion (see sensor::tuning::load_labels). Every combination of given
+// parameters is replayed through the detection logic and scored by precision/recall.
+//
+// Example:
+//   pir-tune --trace garage.csv --labels garage-labels.csv --refresh-rates 50,100,200
+//
+use clap::Parser;
+use pir_motion_sensor::sensor::config::SensorConfig;
+use pir_motion_sensor::sensor::trace::Trace;
+use pir_motion_sensor::sensor::tuning::{TuningRange, load_labels, sweep};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Finds PIR sensor settings for a recorded trace")]
+struct Args {
+    #[arg(long, help = "trace file recorded with TraceRecorder")]
+    trace: PathBuf,
+    #[arg(
+        long,
+        help = "CSV file with intervals of real motion: start,end (miliseconds since trace start)"
+    )]
+    labels: PathBuf,
+    #[arg(
+        long,
+        help = "sensor from the trace, required when trace contains many sensors"
+    )]
+    sensor: Option<String>,
+    #[arg(long, value_delimiter = ',', default_value = "50,100,200")]
+    refresh_rates: Vec<u64>,
+    #[arg(long, value_delimiter = ',', default_value = "500,1000,2000")]
+    motion_periods: Vec<u64>,
+    #[arg(long, value_delimiter = ',', default_value = "1,2,3,4,5")]
+    triggering_numbers: Vec<i16>,
+    #[arg(
+        long,
+        default_value_t = 1000,
+        help = "detection up to this time (miliseconds) after labelled interval still belongs to it"
+    )]
+    tolerance: u64,
+    #[arg(
+        long,
+        default_value_t = 10,
+        help = "number of the best results printed"
+    )]
+    top: usize,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let trace = match Trace::load(&args.trace) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("cannot load trace {}: {e}", args.trace.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let labels = match load_labels(&args.labels) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("cannot load labels {}: {e}", args.labels.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let sensor_name = match args.sensor {
+        Some(name) => name,
+        None if trace.sensors.len() == 1 => trace.sensors.keys().next().unwrap().clone(),
+        None => {
+            let mut names: Vec<&String> = trace.sensors.keys().collect();
+            names.sort();
+            eprintln!("trace contains many sensors, choose one with --sensor: {names:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(sensor_trace) = trace.sensor(&sensor_name) else {
+        eprintln!("sensor {sensor_name} not found in the trace");
+        return ExitCode::FAILURE;
+    };
+
+    let base_config = SensorConfig {
+        name: sensor_name.clone(),
+        ..Default::default()
+    };
+
+    let range = TuningRange {
+        refresh_rate_milisecs: args.refresh_rates,
+        motion_time_period_milisecs: args.motion_periods,
+        minimal_triggering_number: args.triggering_numbers,
+        tolerance_milisecs: args.tolerance,
+    };
+
+    let results = sweep(sensor_trace, &labels, &base_config, &range).await;
+
+    println!(
+        "sensor: {sensor_name}, labelled intervals: {}, evaluated combinations: {}\n",
+        labels.len(),
+        results.len()
+    );
+    println!(
+        "{:>8} {:>8} {:>9} {:>10} {:>5} {:>5} {:>9} {:>7} {:>12}",
+        "refresh",
+        "period",
+        "triggers",
+        "detections",
+        "fp",
+        "miss",
+        "precision",
+        "recall",
+        "latency(ms)"
+    );
+
+    for result in results.iter().take(args.top) {
+        let latency = result
+            .mean_latency
+            .map(|l| l.as_millis().to_string())
+            .unwrap_or_else(|| String::from("-"));
+
+        println!(
+            "{:>8} {:>8} {:>9} {:>10} {:>5} {:>5} {:>9.3} {:>7.3} {:>12}",
+            result.config.refresh_rate_milisecs,
+            result.config.motion_time_period_milisecs,
+            result.config.minimal_triggering_number,
+            result.valid_detections,
+            result.false_positives,
+            result.missed_intervals,
+            result.precision,
+            result.recall,
+            latency
+        );
+    }
+
+    match results.first() {
+        Some(best) if best.f1_score() > 0.0 => {
+            println!("\nrecommended configuration:");
+            println!(
+                "  refresh_rate_milisecs = {}",
+                best.config.refresh_rate_milisecs
+            );
+            println!(
+                "  motion_time_period_milisecs = {}",
+                best.config.motion_time_period_milisecs
+            );
+            println!(
+                "  minimal_triggering_number = {}",
+                best.config.minimal_triggering_number
+            );
+            ExitCode::SUCCESS
+        }
+        _ => {
+            println!("\nno combination detected any labelled motion");
+            ExitCode::FAILURE
+        }
+    }
+}
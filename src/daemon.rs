@@ -0,0 +1,317 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::actions::ActionConfig;
+use crate::rules::{Firing, RuleAction, RuleConfig, RuleEngine, ZoneConfig};
+use crate::sensor::config::SensorConfig;
+use crate::sensor::events::{DetectionEvent, EventKind};
+use crate::sensor::simulation::Scenario;
+use crate::sinks::jsonl::JsonLinesConfig;
+use crate::sinks::mqtt::MqttConfig;
+use crate::sinks::webhook::WebhookConfig;
+
+pub use crate::sinks::jsonl::event_json;
+
+// body of requests sent by "webhook" reactions of rules
+const RULE_PAYLOAD_TEMPLATE: &str = r#"{"rule":"{sensor}","time":"{timestamp}"}"#;
+
+//
+// Config file of pir-motiond (TOML):
+//
+//   [[sensors]]
+//   name = "Garage"
+//   pin_number = 16
+//   refresh_rate_milisecs = 100
+//   motion_time_period_milisecs = 500
+//   minimal_triggering_number = 5
+//
+//   [sensors.health]             # optional, see HealthConfig
+//   stuck_high_milisecs = 60000
+//
+//   [sinks]
+//   stdout_json = true
+//
+//   [sinks.webhook]
+//   urls = ["http://192.168.1.10:8080/motion"]
+//
+//   [sinks.jsonl]                # optional, see JsonLinesConfig
+//   path = "/var/log/pir-motiond/events.jsonl"
+//
+//   [sinks.mqtt]                 # optional, see MqttConfig
+//   host = "192.168.1.10"
+//
+//   [[actions]]                  # optional, see ActionConfig
+//   name = "GarageLight"
+//   sensors = ["Garage"]
+//   pin_number = 21
+//   on_duration_milisecs = 30000
+//
+//   [[zones]] and [[rules]]      # optional, see rules module
+//
+//   [simulation]                 # optional, see Scenario - sensors and actions without hardware
+//
+#[derive(Deserialize, Debug, Clone)]
+pub struct DaemonConfig {
+    pub sensors: Vec<SensorConfig>,
+    #[serde(default)]
+    pub sinks: SinksConfig,
+    #[serde(default)]
+    pub actions: Vec<ActionConfig>,
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    // when set, sensors read simulated inputs and actions drive virtual outputs
+    #[serde(default)]
+    pub simulation: Option<Scenario>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SinksConfig {
+    // every valid detection is printed to stdout as a single JSON line
+    #[serde(default)]
+    pub stdout_json: bool,
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
+    // every event of sensors is appended to a JSON lines file, see JsonLinesConfig
+    #[serde(default)]
+    pub jsonl: Option<JsonLinesConfig>,
+    // every event of sensors is published to MQTT broker, see MqttConfig
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+}
+
+impl DaemonConfig {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let config: DaemonConfig = toml::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        config
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sensors.is_empty() {
+            return Err(String::from("no sensors configured"));
+        }
+
+        let mut names = HashSet::new();
+        let mut pins = HashSet::new();
+        // simulated sensors and virtual outputs don't use GPIO pins
+        let gpio = self.simulation.is_none();
+
+        for sensor in self.sensors.iter() {
+            if !names.insert(sensor.name.as_str()) {
+                return Err(format!("duplicated sensor name: {}", sensor.name));
+            }
+            if gpio && !pins.insert(sensor.pin_number) {
+                return Err(format!(
+                    "pin {} is used by more than one sensor",
+                    sensor.pin_number
+                ));
+            }
+            sensor
+                .validate()
+                .map_err(|e| format!("sensor {}: {e}", sensor.name))?;
+        }
+
+        if let Some(simulation) = self.simulation.as_ref() {
+            let sensor_names: Vec<_> = self.sensors.iter().map(|s| s.name.clone()).collect();
+            simulation.validate(&sensor_names)?;
+        }
+
+        if let Some(mqtt) = self.sinks.mqtt.as_ref() {
+            mqtt.validate().map_err(|e| format!("mqtt: {e}"))?;
+        }
+
+        let mut action_names = HashSet::new();
+
+        for action in self.actions.iter() {
+            if !action_names.insert(action.name.as_str()) {
+                return Err(format!("duplicated action name: {}", action.name));
+            }
+            if gpio && !pins.insert(action.pin_number) {
+                return Err(format!(
+                    "pin {} of action {} is already in use",
+                    action.pin_number, action.name
+                ));
+            }
+            if let Some(sensor) = action.sensors.iter().find(|s| !names.contains(s.as_str())) {
+                return Err(format!("action {}: unknown sensor {sensor}", action.name));
+            }
+        }
+
+        for zone in self.zones.iter() {
+            if names.contains(zone.name.as_str()) {
+                return Err(format!("zone {} has the same name as a sensor", zone.name));
+            }
+            if let Some(sensor) = zone.sensors.iter().find(|s| !names.contains(s.as_str())) {
+                return Err(format!("zone {}: unknown sensor {sensor}", zone.name));
+            }
+        }
+
+        let engine = RuleEngine::new(self.zones.clone(), self.rules.clone())?;
+        if let Some(sensor) = engine.sensors().iter().find(|s| !names.contains(*s)) {
+            return Err(format!("rules: unknown sensor or zone {sensor}"));
+        }
+
+        for rule in self.rules.iter() {
+            for action in rule.then.iter() {
+                match action {
+                    RuleAction::Webhook { .. } => {}
+                    RuleAction::Pulse { pin_number, .. } => {
+                        if gpio && !pins.insert(*pin_number) {
+                            return Err(format!(
+                                "rule {}: pin {pin_number} is already in use",
+                                rule.name
+                            ));
+                        }
+                    }
+                    RuleAction::Action { name } => {
+                        if !action_names.contains(name.as_str()) {
+                            return Err(format!("rule {}: unknown action {name}", rule.name));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    //
+    // "pulse" reactions of rules are executed as actions without sensors, see pulse_action_name()
+    //
+    pub fn pulse_actions(&self) -> Vec<ActionConfig> {
+        self.rules
+            .iter()
+            .flat_map(|rule| {
+                rule.then.iter().filter_map(|action| match action {
+                    RuleAction::Pulse {
+                        pin_number,
+                        duration_milisecs,
+                    } => Some(ActionConfig {
+                        name: pulse_action_name(&rule.name, *pin_number),
+                        sensors: Vec::new(),
+                        pin_number: *pin_number,
+                        on_duration_milisecs: *duration_milisecs,
+                        retrigger_extends: false,
+                        inverted: false,
+                    }),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    //
+    // "webhook" reactions of rules are sent by a separate webhook sink where rule name is used
+    // instead of sensor name. None if no rule has such reaction.
+    //
+    pub fn rules_webhook(&self) -> Option<WebhookConfig> {
+        let mut routes = HashMap::new();
+
+        for rule in self.rules.iter() {
+            let urls: Vec<String> = rule
+                .then
+                .iter()
+                .filter_map(|action| match action {
+                    RuleAction::Webhook { url } => Some(url.clone()),
+                    _ => None,
+                })
+                .collect();
+
+            if !urls.is_empty() {
+                routes.insert(rule.name.clone(), urls);
+            }
+        }
+
+        (!routes.is_empty()).then(|| WebhookConfig {
+            routes,
+            payload_template: String::from(RULE_PAYLOAD_TEMPLATE),
+            ..Default::default()
+        })
+    }
+}
+
+pub fn pulse_action_name(rule_name: &str, pin_number: u8) -> String {
+    format!("{rule_name}/pulse{pin_number}")
+}
+
+pub fn firing_json(firing: &Firing) -> String {
+    serde_json::json!({
+        "event": "rule",
+        "rule": firing.rule,
+        "sensor": firing.sensor_name,
+        "dry_run": firing.dry_run,
+        "time": DateTime::<Utc>::from(firing.time).to_rfc3339(),
+    })
+    .to_string()
+}
+
+// single line printed by "stdout_json" sink
+pub fn detection_json(sensor_name: &str, detection_time: SystemTime) -> String {
+    event_json(&DetectionEvent {
+        sensor_name: sensor_name.to_string(),
+        time: detection_time,
+        kind: EventKind::Detection,
+    })
+}
+
+//
+// systemd integration (Type=notify): sends "state" (for instance READY=1) to the socket from
+// NOTIFY_SOCKET environment variable. Returns false when daemon is not started by systemd.
+//
+pub fn sd_notify(state: &str) -> io::Result<bool> {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+
+    sd_notify_to(&socket_path, state)?;
+    Ok(true)
+}
+
+// sends "state" to the given notification socket, a name starting with @ is an abstract socket
+pub fn sd_notify_to(socket_path: &OsStr, state: &str) -> io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = UnixDatagram::unbound()?;
+    let socket_path = socket_path.to_string_lossy();
+
+    match socket_path.strip_prefix('@') {
+        // abstract socket namespace
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+        }
+        None => {
+            socket.send_to(state.as_bytes(), socket_path.as_ref())?;
+        }
+    }
+
+    Ok(())
+}
+
+// how often WATCHDOG=1 should be sent - half of WATCHDOG_USEC set by systemd, None if disabled
+pub fn sd_watchdog_interval() -> Option<Duration> {
+    let micros: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+
+    // watchdog is meant for this process only
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse::<u32>().ok() != Some(std::process::id())
+    {
+        return None;
+    }
+
+    (micros > 0).then(|| Duration::from_micros(micros / 2))
+}
@@ -0,0 +1,410 @@
+use log::{info, warn};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Deserialize;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+
+use crate::sensor::events::{DetectionEvent, EventKind};
+
+const HOUR_MILISECS: i64 = 60 * 60 * 1000;
+const DAY_MILISECS: i64 = 24 * HOUR_MILISECS;
+
+// how often retention policy is applied by History::run()
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HistoryConfig {
+    // SQLite database file, created if it doesn't exist
+    pub path: PathBuf,
+    // valid detections of the same sensor closer to each other than this time
+    // belong to the same motion episode
+    #[serde(default = "default_episode_gap_milisecs")]
+    pub episode_gap_milisecs: u64, // miliseconds
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    // records older than this are removed, None - records are kept forever
+    pub max_age_days: Option<u32>,
+    // maximum number of rows kept in each table (the oldest are removed first), None - no limit
+    pub max_rows: Option<u64>,
+}
+
+fn default_episode_gap_milisecs() -> u64 {
+    30_000
+}
+
+impl HistoryConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            episode_gap_milisecs: default_episode_gap_milisecs(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub sensor: String,
+    pub time: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    pub sensor: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub detections: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub sensor: String,
+    pub kind: String,
+    pub time: SystemTime,
+}
+
+// aggregation buckets, boundaries are in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Hour,
+    Day,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketCount {
+    pub start: SystemTime,
+    pub count: u64,
+}
+
+#[derive(Debug)]
+pub struct History {
+    config: HistoryConfig,
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(config: HistoryConfig) -> rusqlite::Result<Self> {
+        let connection = Connection::open(&config.path)?;
+
+        // all times are stored as miliseconds since unix epoch
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS detections (
+                 id INTEGER PRIMARY KEY,
+                 sensor TEXT NOT NULL,
+                 time_ms INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS detections_sensor_time ON detections (sensor, time_ms);
+             CREATE TABLE IF NOT EXISTS episodes (
+                 id INTEGER PRIMARY KEY,
+                 sensor TEXT NOT NULL,
+                 start_ms INTEGER NOT NULL,
+                 end_ms INTEGER NOT NULL,
+                 detections INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS episodes_sensor_end ON episodes (sensor, end_ms);
+             CREATE TABLE IF NOT EXISTS faults (
+                 id INTEGER PRIMARY KEY,
+                 sensor TEXT NOT NULL,
+                 kind TEXT NOT NULL,
+                 time_ms INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS faults_sensor_time ON faults (sensor, time_ms);",
+        )?;
+
+        Ok(Self { config, connection })
+    }
+
+    pub fn record_detection(&mut self, sensor: &str, time: SystemTime) -> rusqlite::Result<()> {
+        let time_ms = to_milisecs(time);
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "INSERT INTO detections (sensor, time_ms) VALUES (?1, ?2)",
+            params![sensor, time_ms],
+        )?;
+
+        // detection either extends the latest episode of this sensor or starts a new one
+        let latest_episode: Option<(i64, i64)> = transaction
+            .query_row(
+                "SELECT id, end_ms FROM episodes WHERE sensor = ?1 ORDER BY end_ms DESC LIMIT 1",
+                params![sensor],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        match latest_episode {
+            Some((id, end_ms))
+                if time_ms >= end_ms
+                    && (time_ms - end_ms) as u64 <= self.config.episode_gap_milisecs =>
+            {
+                transaction.execute(
+                    "UPDATE episodes SET end_ms = ?1, detections = detections + 1 WHERE id = ?2",
+                    params![time_ms, id],
+                )?;
+            }
+            _ => {
+                transaction.execute(
+                    "INSERT INTO episodes (sensor, start_ms, end_ms, detections) VALUES (?1, ?2, ?2, 1)",
+                    params![sensor, time_ms],
+                )?;
+            }
+        }
+
+        transaction.commit()
+    }
+
+    pub fn record_fault(
+        &mut self,
+        sensor: &str,
+        kind: &str,
+        time: SystemTime,
+    ) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO faults (sensor, kind, time_ms) VALUES (?1, ?2, ?3)",
+            params![sensor, kind, to_milisecs(time)],
+        )?;
+        Ok(())
+    }
+
+    // records valid detections and faults, other events are not stored
+    pub fn record_event(&mut self, event: &DetectionEvent) -> rusqlite::Result<()> {
+        match event.kind {
+            EventKind::Detection => self.record_detection(&event.sensor_name, event.time),
+            EventKind::Fault(fault) => {
+                self.record_fault(&event.sensor_name, fault.as_str(), event.time)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    //
+    // removes records according to retention policy, returns number of removed rows
+    //
+    pub fn apply_retention(&mut self, now: SystemTime) -> rusqlite::Result<usize> {
+        let retention = self.config.retention.clone();
+        let mut removed = 0;
+
+        if let Some(days) = retention.max_age_days {
+            let oldest_ms = to_milisecs(now) - days as i64 * DAY_MILISECS;
+            removed += self.connection.execute(
+                "DELETE FROM detections WHERE time_ms < ?1",
+                params![oldest_ms],
+            )?;
+            removed += self
+                .connection
+                .execute("DELETE FROM episodes WHERE end_ms < ?1", params![oldest_ms])?;
+            removed += self
+                .connection
+                .execute("DELETE FROM faults WHERE time_ms < ?1", params![oldest_ms])?;
+        }
+
+        if let Some(max_rows) = retention.max_rows {
+            let max_rows = max_rows as i64;
+            removed += self.connection.execute(
+                "DELETE FROM detections WHERE id NOT IN
+                     (SELECT id FROM detections ORDER BY time_ms DESC LIMIT ?1)",
+                params![max_rows],
+            )?;
+            removed += self.connection.execute(
+                "DELETE FROM episodes WHERE id NOT IN
+                     (SELECT id FROM episodes ORDER BY end_ms DESC LIMIT ?1)",
+                params![max_rows],
+            )?;
+            removed += self.connection.execute(
+                "DELETE FROM faults WHERE id NOT IN
+                     (SELECT id FROM faults ORDER BY time_ms DESC LIMIT ?1)",
+                params![max_rows],
+            )?;
+        }
+
+        Ok(removed)
+    }
+
+    //
+    // queries - "sensor: None" means all sensors, time ranges are half-open: [start, end)
+    //
+    pub fn detections(
+        &self,
+        sensor: Option<&str>,
+        range: Range<SystemTime>,
+    ) -> rusqlite::Result<Vec<Detection>> {
+        let mut statement = self.connection.prepare(
+            "SELECT sensor, time_ms FROM detections
+             WHERE (?1 IS NULL OR sensor = ?1) AND time_ms >= ?2 AND time_ms < ?3
+             ORDER BY time_ms",
+        )?;
+
+        statement
+            .query_map(
+                params![sensor, to_milisecs(range.start), to_milisecs(range.end)],
+                |row| {
+                    Ok(Detection {
+                        sensor: row.get(0)?,
+                        time: from_milisecs(row.get(1)?),
+                    })
+                },
+            )?
+            .collect()
+    }
+
+    pub fn last_detection(
+        &self,
+        sensor: Option<&str>,
+        range: Range<SystemTime>,
+    ) -> rusqlite::Result<Option<Detection>> {
+        self.connection
+            .query_row(
+                "SELECT sensor, time_ms FROM detections
+                 WHERE (?1 IS NULL OR sensor = ?1) AND time_ms >= ?2 AND time_ms < ?3
+                 ORDER BY time_ms DESC LIMIT 1",
+                params![sensor, to_milisecs(range.start), to_milisecs(range.end)],
+                |row| {
+                    Ok(Detection {
+                        sensor: row.get(0)?,
+                        time: from_milisecs(row.get(1)?),
+                    })
+                },
+            )
+            .optional()
+    }
+
+    // episodes which overlap with given time range
+    pub fn episodes(
+        &self,
+        sensor: Option<&str>,
+        range: Range<SystemTime>,
+    ) -> rusqlite::Result<Vec<Episode>> {
+        let mut statement = self.connection.prepare(
+            "SELECT sensor, start_ms, end_ms, detections FROM episodes
+             WHERE (?1 IS NULL OR sensor = ?1) AND end_ms >= ?2 AND start_ms < ?3
+             ORDER BY start_ms",
+        )?;
+
+        statement
+            .query_map(
+                params![sensor, to_milisecs(range.start), to_milisecs(range.end)],
+                |row| {
+                    Ok(Episode {
+                        sensor: row.get(0)?,
+                        start: from_milisecs(row.get(1)?),
+                        end: from_milisecs(row.get(2)?),
+                        detections: row.get::<_, i64>(3)? as u64,
+                    })
+                },
+            )?
+            .collect()
+    }
+
+    pub fn faults(
+        &self,
+        sensor: Option<&str>,
+        range: Range<SystemTime>,
+    ) -> rusqlite::Result<Vec<Fault>> {
+        let mut statement = self.connection.prepare(
+            "SELECT sensor, kind, time_ms FROM faults
+             WHERE (?1 IS NULL OR sensor = ?1) AND time_ms >= ?2 AND time_ms < ?3
+             ORDER BY time_ms",
+        )?;
+
+        statement
+            .query_map(
+                params![sensor, to_milisecs(range.start), to_milisecs(range.end)],
+                |row| {
+                    Ok(Fault {
+                        sensor: row.get(0)?,
+                        kind: row.get(1)?,
+                        time: from_milisecs(row.get(2)?),
+                    })
+                },
+            )?
+            .collect()
+    }
+
+    // number of detections per hour or day, buckets without detections are skipped
+    pub fn aggregate(
+        &self,
+        sensor: Option<&str>,
+        range: Range<SystemTime>,
+        bucket: Bucket,
+    ) -> rusqlite::Result<Vec<BucketCount>> {
+        let bucket_milisecs = match bucket {
+            Bucket::Hour => HOUR_MILISECS,
+            Bucket::Day => DAY_MILISECS,
+        };
+
+        let mut statement = self.connection.prepare(
+            "SELECT (time_ms / ?4) * ?4 AS bucket_start, COUNT(*) FROM detections
+             WHERE (?1 IS NULL OR sensor = ?1) AND time_ms >= ?2 AND time_ms < ?3
+             GROUP BY bucket_start ORDER BY bucket_start",
+        )?;
+
+        statement
+            .query_map(
+                params![
+                    sensor,
+                    to_milisecs(range.start),
+                    to_milisecs(range.end),
+                    bucket_milisecs
+                ],
+                |row| {
+                    Ok(BucketCount {
+                        start: from_milisecs(row.get(0)?),
+                        count: row.get::<_, i64>(1)? as u64,
+                    })
+                },
+            )?
+            .collect()
+    }
+
+    //
+    // records valid detections and faults from the channel (see record_event()) until it's
+    // closed or "stop" is cancelled, retention policy is applied at start and then periodically.
+    // Events of sensors can be taken from the event channel or Subscription::into_events().
+    //
+    pub async fn run(mut self, mut events: Receiver<DetectionEvent>, stop: Arc<CancellationToken>) {
+        info!(
+            "history: recording detections and faults to {}",
+            self.config.path.display()
+        );
+
+        let mut retention_check = tokio::time::interval(RETENTION_CHECK_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = stop.cancelled() => break,
+                _ = retention_check.tick() => {
+                    if let Err(e) = self.apply_retention(SystemTime::now()) {
+                        warn!("history: cannot apply retention policy: {e}");
+                    }
+                }
+                event = events.recv() => match event {
+                    Some(event) => {
+                        if let Err(e) = self.record_event(&event) {
+                            warn!("history: cannot record event of {}: {e}", event.sensor_name);
+                        }
+                    }
+                    None => break,
+                },
+            }
+        }
+    }
+}
+
+fn to_milisecs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+fn from_milisecs(milisecs: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(milisecs.max(0) as u64)
+}
@@ -1 +1,13 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "tokio")]
+pub mod actions;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod rules;
 pub mod sensor;
+#[cfg(feature = "std")]
+pub mod sinks;
@@ -0,0 +1,281 @@
+use chrono::{DateTime, Local, NaiveTime};
+use log::{debug, info};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::sensor::events::{DetectionEvent, EventKind};
+
+//
+// Rules connect valid detections with reactions, for instance (TOML):
+//
+//   [[zones]]
+//   name = "Entrance"
+//   sensors = ["MainDoorSlow", "Hall"]
+//
+//   [[rules]]
+//   name = "NightIntruder"
+//   when = ["Garage", "Entrance"]     # sensor or zone names, all of them...
+//   within_milisecs = 10000           # ...within 10 s
+//   schedule = { from = "22:00", to = "06:00" }
+//   rate_limit_milisecs = 60000
+//   then = [
+//     { type = "webhook", url = "http://192.168.1.10:8080/alarm" },
+//     { type = "pulse", pin_number = 21, duration_milisecs = 2000 },
+//   ]
+//
+#[derive(Deserialize, Debug, Clone)]
+pub struct ZoneConfig {
+    pub name: String,
+    pub sensors: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RuleConfig {
+    pub name: String,
+    // sensor or zone names, rule fires when each of them has a valid detection within
+    // "within_milisecs" (a zone has a detection when any of its sensors has one)
+    pub when: Vec<String>,
+    #[serde(default)]
+    pub within_milisecs: u64,
+    // local time, rule doesn't fire outside of it
+    #[serde(default)]
+    pub schedule: Option<ScheduleConfig>,
+    // minimal time between two firings of the rule, 0 - no limit
+    #[serde(default)]
+    pub rate_limit_milisecs: u64,
+    // rule is evaluated and logged, but its reactions are not executed
+    #[serde(default)]
+    pub dry_run: bool,
+    pub then: Vec<RuleAction>,
+}
+
+// "HH:MM" or "HH:MM:SS", "from" later than "to" means the schedule goes through midnight
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScheduleConfig {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    // POST request with rule name and time
+    Webhook {
+        url: String,
+    },
+    // output pin is set on for given time
+    Pulse {
+        pin_number: u8,
+        duration_milisecs: u64,
+    },
+    // action from actions module is turned on, as by a detection of its sensor
+    Action {
+        name: String,
+    },
+}
+
+// rule whose conditions are met by a detection
+#[derive(Debug, Clone, PartialEq)]
+pub struct Firing {
+    pub rule: String,
+    pub time: SystemTime,
+    // sensor whose detection completed the conditions
+    pub sensor_name: String,
+    pub actions: Vec<RuleAction>,
+    // reactions shouldn't be executed
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+struct Rule {
+    config: RuleConfig,
+    schedule: Option<(NaiveTime, NaiveTime)>,
+    // condition (sensor or zone name) -> time of its latest detection
+    detections: HashMap<String, SystemTime>,
+    last_firing: Option<SystemTime>,
+}
+
+impl Rule {
+    fn in_schedule(&self, time: SystemTime) -> bool {
+        let Some((from, to)) = self.schedule else {
+            return true;
+        };
+
+        let local_time = DateTime::<Local>::from(time).time();
+
+        if from <= to {
+            local_time >= from && local_time < to
+        } else {
+            local_time >= from || local_time < to
+        }
+    }
+
+    fn rate_limited(&self, time: SystemTime) -> bool {
+        let rate_limit = Duration::from_millis(self.config.rate_limit_milisecs);
+
+        self.last_firing.is_some_and(|last_firing| {
+            time.duration_since(last_firing).unwrap_or_default() < rate_limit
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RuleEngine {
+    zones: HashMap<String, Vec<String>>,
+    rules: Vec<Rule>,
+    // every rule is treated as "dry_run"
+    dry_run: bool,
+}
+
+impl RuleEngine {
+    pub fn new(zones: Vec<ZoneConfig>, rules: Vec<RuleConfig>) -> Result<Self, String> {
+        let zones: HashMap<String, Vec<String>> =
+            zones.into_iter().map(|z| (z.name, z.sensors)).collect();
+
+        let mut parsed_rules = Vec::new();
+        for config in rules {
+            if config.when.is_empty() {
+                return Err(format!("rule {}: no conditions", config.name));
+            }
+            if parsed_rules
+                .iter()
+                .any(|r: &Rule| r.config.name == config.name)
+            {
+                return Err(format!("duplicated rule name: {}", config.name));
+            }
+
+            let schedule = match config.schedule.as_ref() {
+                Some(s) => Some((
+                    parse_time(&s.from).map_err(|e| format!("rule {}: {e}", config.name))?,
+                    parse_time(&s.to).map_err(|e| format!("rule {}: {e}", config.name))?,
+                )),
+                None => None,
+            };
+
+            parsed_rules.push(Rule {
+                config,
+                schedule,
+                detections: HashMap::new(),
+                last_firing: None,
+            });
+        }
+
+        Ok(Self {
+            zones,
+            rules: parsed_rules,
+            dry_run: false,
+        })
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &RuleConfig> {
+        self.rules.iter().map(|r| &r.config)
+    }
+
+    // sensors used by conditions of rules, either directly or through zones
+    pub fn sensors(&self) -> Vec<&str> {
+        let mut sensors: Vec<&str> = self
+            .rules
+            .iter()
+            .flat_map(|r| r.config.when.iter())
+            .flat_map(|condition| match self.zones.get(condition) {
+                Some(zone_sensors) => zone_sensors.iter().map(String::as_str).collect(),
+                None => vec![condition.as_str()],
+            })
+            .collect();
+
+        sensors.sort();
+        sensors.dedup();
+        sensors
+    }
+
+    // rules fired by the event, only valid detections are taken into account
+    pub fn handle_event(&mut self, event: &DetectionEvent) -> Vec<Firing> {
+        match event.kind {
+            EventKind::Detection => self.handle_detection(&event.sensor_name, event.time),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn handle_detection(&mut self, sensor_name: &str, time: SystemTime) -> Vec<Firing> {
+        let mut firings = Vec::new();
+
+        for rule in self.rules.iter_mut() {
+            let matched: Vec<&String> = rule
+                .config
+                .when
+                .iter()
+                .filter(|condition| {
+                    *condition == sensor_name
+                        || self
+                            .zones
+                            .get(*condition)
+                            .is_some_and(|sensors| sensors.iter().any(|s| s == sensor_name))
+                })
+                .collect();
+
+            if matched.is_empty() {
+                continue;
+            }
+
+            for condition in matched {
+                rule.detections.insert(condition.clone(), time);
+            }
+
+            let within = Duration::from_millis(rule.config.within_milisecs);
+            let all_detected = rule.config.when.iter().all(|condition| {
+                rule.detections
+                    .get(condition)
+                    .is_some_and(|t| time.duration_since(*t).unwrap_or_default() <= within)
+            });
+
+            if !all_detected {
+                continue;
+            }
+
+            if !rule.in_schedule(time) {
+                debug!("rule {}: outside of schedule", rule.config.name);
+                continue;
+            }
+
+            if rule.rate_limited(time) {
+                debug!("rule {}: rate limited", rule.config.name);
+                continue;
+            }
+
+            // the next firing needs all conditions to be met again
+            rule.detections.clear();
+            rule.last_firing = Some(time);
+
+            let dry_run = self.dry_run || rule.config.dry_run;
+            if dry_run {
+                info!(
+                    "rule {} fired by {sensor_name} (dry run, nothing executed)",
+                    rule.config.name
+                );
+            } else {
+                info!("rule {} fired by {sensor_name}", rule.config.name);
+            }
+
+            firings.push(Firing {
+                rule: rule.config.name.clone(),
+                time,
+                sensor_name: sensor_name.to_string(),
+                actions: rule.config.then.clone(),
+                dry_run,
+            });
+        }
+
+        firings
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|_| format!("invalid time in schedule: {time}"))
+}
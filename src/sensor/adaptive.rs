@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+use super::config::AdaptiveConfig;
+
+//
+// Learns the noise floor of a sensor, see AdaptiveConfig. Windows with valid detections are not
+// used - there was a real motion, so triggers are not noise.
+//
+#[derive(Debug, Clone)]
+pub struct Adaptation {
+    config: AdaptiveConfig,
+    // effective minimal_triggering_number
+    current: i16,
+    window_start: Instant,
+    noise_triggers: u64,
+    motion: bool,
+}
+
+impl Adaptation {
+    // "initial" is limited to bounds from config
+    pub fn new(config: AdaptiveConfig, initial: i16, now: Instant) -> Self {
+        Self {
+            config,
+            current: limit(&config, initial),
+            window_start: now,
+            noise_triggers: 0,
+            motion: false,
+        }
+    }
+
+    pub fn current(&self) -> i16 {
+        self.current
+    }
+
+    // starts a new observation window, for instance after the clock was changed
+    pub fn restart(&mut self, now: Instant) {
+        self.window_start = now;
+        self.noise_triggers = 0;
+        self.motion = false;
+    }
+
+    //
+    // "triggers" counted towards minimal_triggering_number since the previous call. Returns new
+    // effective minimal_triggering_number when it's changed at the end of a window.
+    //
+    pub fn update(&mut self, now: Instant, triggers: u64, valid_detection: bool) -> Option<i16> {
+        self.noise_triggers += triggers;
+        self.motion |= valid_detection;
+
+        if now.saturating_duration_since(self.window_start)
+            < Duration::from_millis(self.config.window_milisecs)
+        {
+            return None;
+        }
+
+        let quiet = !self.motion;
+        let noise_triggers = self.noise_triggers;
+        self.restart(now);
+
+        if !quiet {
+            return None;
+        }
+
+        let next = if noise_triggers > self.config.raise_above {
+            self.current + 1
+        } else if noise_triggers <= self.config.lower_at_most {
+            self.current - 1
+        } else {
+            self.current
+        };
+        let next = limit(&self.config, next);
+
+        if next == self.current {
+            return None;
+        }
+
+        self.current = next;
+        Some(next)
+    }
+}
+
+// like clamp(), but doesn't panic when bounds are swapped (config which didn't go through
+// AdaptiveConfig::validate()) - max_triggering_number wins then
+fn limit(config: &AdaptiveConfig, value: i16) -> i16 {
+    value
+        .max(config.min_triggering_number)
+        .min(config.max_triggering_number)
+}
@@ -0,0 +1,115 @@
+use rppal::gpio::Mode::Input;
+use rppal::gpio::{self, Gpio, IoPin, OutputPin};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::config::Bias;
+
+//
+// source of sensor output state - real GPIO pin or anything which pretends to be one
+// (recorded traces, simulations). Backend is read by MotionSensor::reading_from_sensor()
+// and high states are processed exactly the same way regardless of their origin.
+//
+pub trait InputBackend: Debug + Send {
+    // true - sensor output is in the high state (motion detected) at "now"
+    fn is_high(&mut self, now: Instant) -> bool;
+
+    // pull-up/pull-down resistor from SensorConfig, backends without a physical line ignore it.
+    // Bias::None means the bias configured outside (for instance in config.txt) is left alone.
+    fn set_bias(&mut self, _bias: Bias) {}
+}
+
+// pin is reset to the mode it had before it was opened (and bias set by set_bias() is disabled)
+// when GpioInput is dropped
+#[derive(Debug)]
+pub struct GpioInput {
+    pin: IoPin,
+}
+
+impl GpioInput {
+    pub fn new(pin: IoPin) -> Self {
+        Self { pin }
+    }
+
+    // opens GPIO pin as an input, fails when GPIO is not available or pin is already in use
+    pub fn open(pin_number: u8) -> rppal::gpio::Result<Self> {
+        let pin = Gpio::new()?.get(pin_number)?.into_io(Input);
+        Ok(Self::new(pin))
+    }
+}
+
+impl InputBackend for GpioInput {
+    fn is_high(&mut self, _now: Instant) -> bool {
+        self.pin.is_high()
+    }
+
+    fn set_bias(&mut self, bias: Bias) {
+        match bias {
+            Bias::None => {}
+            Bias::PullUp => self.pin.set_bias(gpio::Bias::PullUp),
+            Bias::PullDown => self.pin.set_bias(gpio::Bias::PullDown),
+        }
+    }
+}
+
+//
+// destination of output state - GPIO pin driving a relay/light or anything which pretends to be
+// one, see actions module
+//
+pub trait OutputBackend: Debug + Send {
+    // physical state of the output, inversion (active low relays) is already applied by caller
+    fn set_high(&mut self, high: bool);
+}
+
+#[derive(Debug)]
+pub struct GpioOutput {
+    pin: OutputPin,
+}
+
+impl GpioOutput {
+    pub fn new(pin: OutputPin) -> Self {
+        Self { pin }
+    }
+
+    // opens GPIO pin as an output (low), fails when GPIO is not available or pin is already in use
+    pub fn open(pin_number: u8) -> rppal::gpio::Result<Self> {
+        let pin = Gpio::new()?.get(pin_number)?.into_output_low();
+        Ok(Self::new(pin))
+    }
+}
+
+impl OutputBackend for GpioOutput {
+    fn set_high(&mut self, high: bool) {
+        if high {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+    }
+}
+
+//
+// output which only remembers its state - for tests and setups without hardware,
+// clones share the same state
+//
+#[derive(Debug, Clone, Default)]
+pub struct VirtualOutput {
+    high: Arc<Mutex<bool>>,
+}
+
+impl VirtualOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_high(&self) -> bool {
+        *self.high.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl OutputBackend for VirtualOutput {
+    fn set_high(&mut self, high: bool) {
+        *self.high.lock().unwrap_or_else(|e| e.into_inner()) = high;
+    }
+}
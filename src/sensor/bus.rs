@@ -0,0 +1,177 @@
+use std::fmt;
+use std::time::SystemTime;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::{self, Receiver};
+use tracing::warn;
+
+use super::events::{DetectionEvent, EventType};
+
+//
+// Events of many sensors delivered to many independent subscribers (logger, sinks, alarm
+// logic, UI). Sensors publish to the bus set by MotionSensor::set_event_bus(). Every
+// subscriber has its own buffer of "capacity" events - when it's not read fast enough, the
+// oldest events are dropped and the subscriber gets BusError::Lagged with their number.
+//
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DetectionEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusError {
+    // number of events dropped since the previous recv() because subscriber was too slow
+    Lagged(u64),
+    // every publisher (bus and its clones) is dropped
+    Closed,
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::Lagged(n) => write!(f, "subscriber lagged behind, {n} event(s) dropped"),
+            BusError::Closed => write!(f, "event bus closed"),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    // returns number of subscribers which will receive the event
+    pub fn publish(&self, event: DetectionEvent) -> usize {
+        self.sender.send(event).unwrap_or_default()
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        self.subscribe_filtered(EventFilter::default())
+    }
+
+    pub fn subscribe_filtered(&self, filter: EventFilter) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            filter,
+            lagged: 0,
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+//
+// Empty filter passes every event, otherwise event has to match one of sensors (if any is given)
+// and one of types (if any is given)
+//
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    sensors: Vec<String>,
+    types: Vec<EventType>,
+}
+
+impl EventFilter {
+    pub fn sensor(mut self, sensor_name: &str) -> Self {
+        self.sensors.push(sensor_name.to_string());
+        self
+    }
+
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.types.push(event_type);
+        self
+    }
+
+    pub fn matches(&self, event: &DetectionEvent) -> bool {
+        (self.sensors.is_empty() || self.sensors.contains(&event.sensor_name))
+            && (self.types.is_empty() || self.types.contains(&event.kind.event_type()))
+    }
+}
+
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: broadcast::Receiver<DetectionEvent>,
+    filter: EventFilter,
+    lagged: u64,
+}
+
+impl Subscription {
+    //
+    // next event which matches the filter. After BusError::Lagged subscription can be still
+    // used, the next call returns the oldest event which is still available.
+    //
+    pub async fn recv(&mut self) -> Result<DetectionEvent, BusError> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Ok(event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(n)) => {
+                    self.lagged += n;
+                    warn!("event bus subscriber lagged behind, {n} event(s) dropped");
+                    return Err(BusError::Lagged(n));
+                }
+                Err(RecvError::Closed) => return Err(BusError::Closed),
+            }
+        }
+    }
+
+    // number of events dropped so far because subscriber was too slow
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+
+    pub(crate) fn into_parts(self) -> (broadcast::Receiver<DetectionEvent>, EventFilter) {
+        (self.receiver, self.filter)
+    }
+
+    //
+    // valid detections as (sensor name, time) - for sinks which read such channel, for instance
+    // WebhookSink::run(). Forwarding stops when the bus is closed or the returned receiver is
+    // dropped.
+    //
+    pub fn into_detections(mut self, capacity: usize) -> Receiver<(String, SystemTime)> {
+        let (sender, receiver) = mpsc::channel(capacity);
+
+        tokio::spawn(async move {
+            loop {
+                match self.recv().await {
+                    Ok(event) if event.kind.event_type() != EventType::Detection => continue,
+                    Ok(event) => {
+                        if sender.send((event.sensor_name, event.time)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(BusError::Lagged(_)) => continue,
+                    Err(BusError::Closed) => break,
+                }
+            }
+        });
+
+        receiver
+    }
+
+    // the same as into_detections(), but all events of the subscription are forwarded - for
+    // sinks which read events, for instance JsonLinesSink::run() or History::run()
+    pub fn into_events(mut self, capacity: usize) -> Receiver<DetectionEvent> {
+        let (sender, receiver) = mpsc::channel(capacity);
+
+        tokio::spawn(async move {
+            loop {
+                match self.recv().await {
+                    Ok(event) => {
+                        if sender.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(BusError::Lagged(_)) => continue,
+                    Err(BusError::Closed) => break,
+                }
+            }
+        });
+
+        receiver
+    }
+}
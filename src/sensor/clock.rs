@@ -0,0 +1,109 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+//
+// Source of time for detection logic. Sensors never call Instant::now() or SystemTime::now()
+// directly, so tests (or replays) can run them in virtual time.
+//
+pub trait Clock: Debug + Send + Sync {
+    // monotonic time used for refresh rate, motion time period, etc.
+    fn now(&self) -> Instant;
+    // wall clock time attached to valid detections
+    fn system_now(&self) -> SystemTime;
+}
+
+//
+// Default clock - follows tokio time, so it behaves like a normal clock in production, but
+// in tests it's driven by tokio::time::pause() / tokio::time::advance(). Wall clock time is
+// read from the system each time, so a time step after startup (NTP sync on a Raspberry Pi
+// without RTC) is picked up - unless the clock was created with paused().
+//
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock {
+    // set by paused(): tokio time and wall clock time when the clock was created
+    started_at: Option<(tokio::time::Instant, SystemTime)>,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioClock {
+    pub fn new() -> Self {
+        Self { started_at: None }
+    }
+
+    //
+    // for tests with paused tokio time: wall clock time is counted from the moment when the
+    // clock was created and moves together with virtual time, so valid detections have exact
+    // timestamps
+    //
+    pub fn paused() -> Self {
+        Self {
+            started_at: Some((tokio::time::Instant::now(), SystemTime::now())),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        match self.started_at {
+            Some((started_at, started_at_system)) => started_at_system + started_at.elapsed(),
+            None => SystemTime::now(),
+        }
+    }
+}
+
+//
+// Clock which moves only when advance() is called, clones share the same time
+//
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    elapsed: Arc<Mutex<Duration>>,
+    started_at: Instant,
+    started_at_system: SystemTime,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+
+    // wall clock time reported by the clock before the first advance()
+    pub fn starting_at(system_time: SystemTime) -> Self {
+        Self {
+            elapsed: Arc::new(Mutex::new(Duration::ZERO)),
+            started_at: Instant::now(),
+            started_at_system: system_time,
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+
+    // time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.started_at + self.elapsed()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.started_at_system + self.elapsed()
+    }
+}
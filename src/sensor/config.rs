@@ -1,10 +1,160 @@
 use serde::Deserialize;
 
-#[derive(Deserialize, Debug, Clone)]
+use super::detector::{MAX_PENDING_CAPACITY, OverflowPolicy};
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct SensorConfig {
     pub name: String,
     pub pin_number: u8,
     pub refresh_rate_milisecs: u64,       // miliseconds
     pub motion_time_period_milisecs: u64, // miliseconds
     pub minimal_triggering_number: i16,
+    // after initialization sensor counts triggers but never validates them for this time,
+    // HC-SR501 gives spurious triggers for about a minute after power-up
+    #[serde(default)]
+    pub warmup_milisecs: u64,
+    #[serde(default)]
+    pub health: HealthConfig,
+    // when set, minimal_triggering_number is adjusted to the noise of the sensor
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveConfig>,
+    // high readings which can wait for processing, None - PENDING_CAPACITY
+    #[serde(default)]
+    pub buffer_capacity: Option<u8>,
+    // what happens with a high reading when the buffer is full
+    #[serde(default)]
+    pub overflow_policy: OverflowPolicy,
+    // internal resistor of GPIO pin, it must pull the line to the inactive level
+    #[serde(default)]
+    pub bias: Bias,
+    // level of sensor output which means motion
+    #[serde(default)]
+    pub active_level: ActiveLevel,
+}
+
+impl SensorConfig {
+    // settings which cannot work together, SensorManager and pir-motiond check them before start
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.bias, self.active_level) {
+            (Bias::PullUp, ActiveLevel::High) | (Bias::PullDown, ActiveLevel::Low) => {
+                return Err(String::from(
+                    "bias pulls the line to the active level - disconnected sensor would give motion all the time",
+                ));
+            }
+            _ => {}
+        }
+
+        if self
+            .buffer_capacity
+            .is_some_and(|capacity| !(1..=MAX_PENDING_CAPACITY).contains(&capacity))
+        {
+            return Err(format!(
+                "buffer_capacity must be between 1 and {MAX_PENDING_CAPACITY}"
+            ));
+        }
+
+        if let Some(adaptive) = self.adaptive.as_ref() {
+            adaptive.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+//
+// Internal pull-up/pull-down resistor of GPIO pin. Floating line (long cable, disconnected
+// sensor) picks up noise and gives phantom triggers, bias keeps it at a known level. Sensors
+// with active high output (HC-SR501 and most PIRs) need PullDown.
+//
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Bias {
+    #[default]
+    None,
+    PullUp,
+    PullDown,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ActiveLevel {
+    // output is high during motion (HC-SR501 and most PIRs)
+    #[default]
+    High,
+    // output is low during motion (open collector outputs, some microwave sensors)
+    Low,
+}
+
+//
+// Health supervision of a sensor, every fault condition is disabled by default.
+// Faults are reported as events (see MotionSensor::set_event_channel) and cleared when sensor
+// behaves normally again.
+//
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct HealthConfig {
+    // output is high continuously for longer than this - broken sensor or wiring
+    #[serde(default)]
+    pub stuck_high_milisecs: Option<u64>,
+    // output was not high at all for longer than this - dead sensor or disconnected cable
+    #[serde(default)]
+    pub no_activity_milisecs: Option<u64>,
+    // when true, sensor doesn't produce valid detections while any fault is active
+    #[serde(default)]
+    pub quarantine: bool,
+}
+
+//
+// Adaptive sensitivity: triggers are counted during observation windows without valid
+// detections (the noise floor of the sensor) and the effective minimal_triggering_number is
+// raised or lowered by one after each such window, within given bounds. Every adjustment is
+// reported as SensitivityAdjusted event.
+//
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveConfig {
+    pub min_triggering_number: i16,
+    pub max_triggering_number: i16,
+    // length of observation window, 10 minutes by default
+    #[serde(default = "default_window_milisecs")]
+    pub window_milisecs: u64,
+    // minimal_triggering_number is raised when there were more noise triggers in a window
+    #[serde(default = "default_raise_above")]
+    pub raise_above: u64,
+    // minimal_triggering_number is lowered when there were at most this many noise triggers
+    #[serde(default)]
+    pub lower_at_most: u64,
+}
+
+fn default_window_milisecs() -> u64 {
+    600_000
+}
+
+fn default_raise_above() -> u64 {
+    10
+}
+
+impl AdaptiveConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_triggering_number < 1 {
+            return Err(String::from(
+                "adaptive: min_triggering_number must be at least 1",
+            ));
+        }
+        if self.min_triggering_number > self.max_triggering_number {
+            return Err(String::from(
+                "adaptive: min_triggering_number is greater than max_triggering_number",
+            ));
+        }
+        if self.window_milisecs == 0 {
+            return Err(String::from(
+                "adaptive: window_milisecs must be greater than 0",
+            ));
+        }
+        if self.lower_at_most >= self.raise_above {
+            return Err(String::from(
+                "adaptive: lower_at_most must be lower than raise_above",
+            ));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,349 @@
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use super::config::SensorConfig;
+
+// default number of high readings which can wait for processing, see DetectorConfig
+pub const PENDING_CAPACITY: u8 = 10;
+// larger buffer_capacity is limited to this
+pub const MAX_PENDING_CAPACITY: u8 = 64;
+
+//
+// Detection logic without any runtime: sensor output samples go in, events come out. It doesn't
+// allocate, read any clock or block - time is given by the caller as a Duration since any fixed
+// moment (for instance start of the program), so it can be driven from a plain thread loop,
+// async-std, embassy or a test with made up timestamps. It's available without std as well, see
+// EmbeddedDetector for microcontrollers.
+//
+// MotionSensor is the tokio glue around it: it reads the input backend, supervises health and
+// sends events to channels and the bus.
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectorConfig {
+    pub refresh_rate: Duration,
+    pub motion_time_period: Duration,
+    pub minimal_triggering_number: i16,
+    pub warmup: Duration,
+    // high readings which can wait for processing (1 - MAX_PENDING_CAPACITY)
+    pub buffer_capacity: u8,
+    // what happens with a high reading when the buffer is full
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            refresh_rate: Duration::ZERO,
+            motion_time_period: Duration::ZERO,
+            minimal_triggering_number: 0,
+            warmup: Duration::ZERO,
+            buffer_capacity: PENDING_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+        }
+    }
+}
+
+//
+// High readings wait in a buffer until they are processed (one per refresh rate). Sensor output
+// is read more often than that, so during a longer motion the buffer is full and each policy
+// decides which readings are kept.
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "snake_case"))]
+pub enum OverflowPolicy {
+    // the new reading is dropped
+    #[default]
+    DropNewest,
+    // the oldest waiting reading is dropped, so the buffer holds the most recent ones
+    DropOldest,
+    // readings closer than refresh rate to the newest waiting one are merged into it, so
+    // a continuous motion gives a single waiting reading per refresh. The new reading is
+    // dropped when the buffer is full anyway.
+    Coalesce,
+    // nothing is dropped - sample() refuses the reading and the caller should not read sensor
+    // output until there is room again, see is_full()
+    Block,
+}
+
+#[cfg(feature = "std")]
+impl From<&SensorConfig> for DetectorConfig {
+    fn from(config: &SensorConfig) -> Self {
+        Self {
+            refresh_rate: Duration::from_millis(config.refresh_rate_milisecs),
+            motion_time_period: Duration::from_millis(config.motion_time_period_milisecs),
+            minimal_triggering_number: config.minimal_triggering_number,
+            warmup: Duration::from_millis(config.warmup_milisecs),
+            buffer_capacity: config.buffer_capacity.unwrap_or(PENDING_CAPACITY),
+            overflow_policy: config.overflow_policy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreEvent {
+    // warm-up period has ended, only when DetectorConfig::warmup is not zero
+    Ready,
+    // valid detection
+    Detection,
+}
+
+#[derive(Debug, Clone)]
+pub struct DetectionCore {
+    config: DetectorConfig,
+    // times of high readings waiting for processing (ring buffer), at most buffer_capacity
+    pending: [Duration; MAX_PENDING_CAPACITY as usize],
+    pending_start: u8,
+    pending_len: u8,
+    trigger_count: i16,
+    // time of the reading which started counting towards the next detection
+    first_trigger: Duration,
+    last_check: Duration,
+    ready: bool,
+    // triggers processed during warm-up period, they are never validated
+    warmup_triggers: u64,
+    // triggers counted towards minimal_triggering_number
+    pre_detections: u64,
+    // high readings dropped because the buffer was full
+    dropped_readings: u64,
+    // high readings merged into a waiting one, see OverflowPolicy::Coalesce
+    coalesced_readings: u64,
+}
+
+impl DetectionCore {
+    // warm-up period and the first refresh are counted from Duration::ZERO
+    pub fn new(config: DetectorConfig) -> Self {
+        Self {
+            config,
+            pending: [Duration::ZERO; MAX_PENDING_CAPACITY as usize],
+            pending_start: 0,
+            pending_len: 0,
+            trigger_count: 0,
+            first_trigger: Duration::ZERO,
+            last_check: Duration::ZERO,
+            ready: false,
+            warmup_triggers: 0,
+            pre_detections: 0,
+            dropped_readings: 0,
+            coalesced_readings: 0,
+        }
+    }
+
+    pub fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    // changes sensitivity on the fly, counting towards a valid detection continues
+    pub fn set_minimal_triggering_number(&mut self, minimal_triggering_number: i16) {
+        self.config.minimal_triggering_number = minimal_triggering_number;
+    }
+
+    //
+    // one reading of sensor output at "now", returns false when a high reading was dropped or
+    // refused because the buffer was full - see OverflowPolicy
+    //
+    pub fn sample(&mut self, now: Duration, high: bool) -> bool {
+        if !high {
+            return true;
+        }
+
+        if self.config.overflow_policy == OverflowPolicy::Coalesce
+            && let Some(newest) = self.newest_pending()
+            && now.saturating_sub(newest) < self.config.refresh_rate
+        {
+            self.coalesced_readings += 1;
+            return true;
+        }
+
+        if !self.is_full() {
+            self.push_pending(now);
+            return true;
+        }
+
+        match self.config.overflow_policy {
+            OverflowPolicy::DropOldest => {
+                self.pop_pending();
+                self.push_pending(now);
+            }
+            OverflowPolicy::DropNewest | OverflowPolicy::Coalesce => {}
+            OverflowPolicy::Block => return false,
+        }
+
+        self.dropped_readings += 1;
+        false
+    }
+
+    //
+    // processes at most one pending reading per refresh rate - call it as often as you want,
+    // nothing happens when it's too early
+    //
+    pub fn poll(&mut self, now: Duration) -> Option<CoreEvent> {
+        if !self.ready && !self.is_warming_up(now) {
+            self.ready = true;
+
+            // readings taken during warm-up would keep the buffer busy after it, they are
+            // discarded right away
+            while let Some(reading) = self.oldest_pending()
+                && self.is_warming_up(reading)
+            {
+                self.pop_pending();
+                self.warmup_triggers += 1;
+            }
+
+            if !self.config.warmup.is_zero() {
+                return Some(CoreEvent::Ready);
+            }
+        }
+
+        if !self.is_due(now) {
+            return None;
+        }
+        self.last_check = now;
+
+        // nothing to process
+        let reading = self.pop_pending()?;
+
+        //
+        // readings taken during warm-up are never validated, even when they waited in the buffer
+        // and are processed after it. Counting for valid detection starts from zero after warm-up.
+        //
+        if !self.ready || self.is_warming_up(reading) {
+            self.warmup_triggers += 1;
+            self.trigger_count = 0;
+            return None;
+        }
+
+        //
+        // all triggers of a valid detection must be read within motion time period - readings
+        // may wait in the buffer, so it's checked with times of readings, not of processing
+        //
+        if self.trigger_count <= 0
+            || reading.saturating_sub(self.first_trigger) > self.config.motion_time_period
+        {
+            self.trigger_count = 0;
+            self.first_trigger = reading;
+        }
+
+        self.trigger_count += 1;
+        self.pre_detections += 1;
+
+        // each processed trigger stands for one refresh period of motion
+        if self.config.refresh_rate * self.trigger_count as u32 > self.config.motion_time_period {
+            // this is a new detection - reset counter
+            self.trigger_count = 1;
+            self.first_trigger = reading;
+        }
+
+        if self.trigger_count >= self.config.minimal_triggering_number {
+            // next detection will be counted from zero again
+            self.trigger_count = 0;
+            return Some(CoreEvent::Detection);
+        }
+
+        None
+    }
+
+    //
+    // processes the next pending reading right away, as if refresh rate has already passed - for
+    // instance when the sensor is shut down and pending readings would be lost otherwise
+    //
+    pub fn flush(&mut self) -> Option<CoreEvent> {
+        self.poll(self.last_check + self.config.refresh_rate + Duration::from_millis(1))
+    }
+
+    // sample() and poll() together, for loops which read the sensor at refresh rate
+    pub fn update(&mut self, now: Duration, high: bool) -> Option<CoreEvent> {
+        self.sample(now, high);
+        self.poll(now)
+    }
+
+    // true when refresh rate has passed since the last processing
+    pub fn is_due(&self, now: Duration) -> bool {
+        now.saturating_sub(self.last_check).as_millis() > self.config.refresh_rate.as_millis()
+    }
+
+    pub fn is_warming_up(&self, now: Duration) -> bool {
+        now < self.config.warmup
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    // triggers counted so far towards minimal_triggering_number
+    pub fn trigger_count(&self) -> i16 {
+        self.trigger_count
+    }
+
+    // the last time when a pending reading was processed (or could be, if there was any)
+    pub fn last_check(&self) -> Duration {
+        self.last_check
+    }
+
+    // number of high readings waiting for processing
+    pub fn pending(&self) -> u8 {
+        self.pending_len
+    }
+
+    // time of the oldest high reading waiting for processing
+    pub fn oldest_pending(&self) -> Option<Duration> {
+        (self.pending_len > 0).then(|| self.pending[self.pending_start as usize])
+    }
+
+    // buffer_capacity from config, limited to 1 - MAX_PENDING_CAPACITY
+    pub fn capacity(&self) -> u8 {
+        self.config.buffer_capacity.clamp(1, MAX_PENDING_CAPACITY)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending_len >= self.capacity()
+    }
+
+    pub fn warmup_triggers(&self) -> u64 {
+        self.warmup_triggers
+    }
+
+    pub fn pre_detections(&self) -> u64 {
+        self.pre_detections
+    }
+
+    pub fn dropped_readings(&self) -> u64 {
+        self.dropped_readings
+    }
+
+    pub fn coalesced_readings(&self) -> u64 {
+        self.coalesced_readings
+    }
+
+    // drops pending readings, for instance when they are not valid anymore because of a fault
+    pub fn clear_pending(&mut self) {
+        self.pending_len = 0;
+    }
+
+    fn newest_pending(&self) -> Option<Duration> {
+        (self.pending_len > 0).then(|| self.pending[self.pending_index(self.pending_len - 1)])
+    }
+
+    fn push_pending(&mut self, at: Duration) {
+        self.pending[self.pending_index(self.pending_len)] = at;
+        self.pending_len += 1;
+    }
+
+    fn pop_pending(&mut self) -> Option<Duration> {
+        let oldest = self.oldest_pending()?;
+        self.pending_start = (self.pending_start + 1) % MAX_PENDING_CAPACITY;
+        self.pending_len -= 1;
+        Some(oldest)
+    }
+
+    fn pending_index(&self, offset: u8) -> usize {
+        ((self.pending_start as usize) + offset as usize) % MAX_PENDING_CAPACITY as usize
+    }
+
+    // for callers which keep the counter themselves, see MotionSensor::process_detections()
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_trigger_state(&mut self, trigger_count: i16, last_check: Duration) {
+        self.trigger_count = trigger_count;
+        self.last_check = last_check;
+    }
+}
@@ -0,0 +1,57 @@
+use core::time::Duration;
+use embedded_hal::digital::InputPin;
+
+use super::detector::{CoreEvent, DetectionCore, DetectorConfig};
+
+//
+// Monotonic time of a microcontroller (timer, RTC, embassy Instant, etc.), it may start from
+// any moment but must never go back
+//
+pub trait MonotonicClock {
+    fn now(&mut self) -> Duration;
+}
+
+//
+// Detection core driven by any embedded-hal 1.0 input pin, for boards like RP2040 or ESP32.
+// It's no_std and gives the same valid detections as MotionSensor on Raspberry Pi for the same
+// DetectorConfig. Call poll() in the main loop (or from a timer interrupt), at least once per
+// refresh rate.
+//
+#[derive(Debug)]
+pub struct EmbeddedDetector<P: InputPin, C: MonotonicClock> {
+    pin: P,
+    clock: C,
+    core: DetectionCore,
+    // time of the core (and warm-up period) is counted from this moment
+    started_at: Duration,
+}
+
+impl<P: InputPin, C: MonotonicClock> EmbeddedDetector<P, C> {
+    pub fn new(pin: P, mut clock: C, config: DetectorConfig) -> Self {
+        let started_at = clock.now();
+
+        Self {
+            pin,
+            clock,
+            core: DetectionCore::new(config),
+            started_at,
+        }
+    }
+
+    // reads the pin once and processes pending readings
+    pub fn poll(&mut self) -> Result<Option<CoreEvent>, P::Error> {
+        let high = self.pin.is_high()?;
+        let now = self.clock.now().saturating_sub(self.started_at);
+
+        Ok(self.core.update(now, high))
+    }
+
+    pub fn core(&self) -> &DetectionCore {
+        &self.core
+    }
+
+    // gives back the pin and the clock
+    pub fn release(self) -> (P, C) {
+        (self.pin, self.clock)
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::SystemTime;
+
+//
+// Everything what happens with a sensor. Valid detections are still sent to detection_channel
+// as (name, time), events (including valid detections) are sent to the channel set by
+// MotionSensor::set_event_channel() and published to the bus set by set_event_bus().
+//
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionEvent {
+    pub sensor_name: String,
+    pub time: SystemTime,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    // valid detection
+    Detection,
+    // warm-up period has ended, see SensorConfig::warmup_milisecs
+    Ready,
+    // sensor doesn't behave like a working sensor, see HealthConfig
+    Fault(FaultKind),
+    // sensor behaves normally again
+    FaultCleared(FaultKind),
+    // sensor was started by SensorManager
+    SensorAdded,
+    // sensor was stopped by SensorManager and its pin is released
+    SensorRemoved,
+    // effective minimal_triggering_number was changed, see AdaptiveConfig
+    SensitivityAdjusted { from: i16, to: i16 },
+}
+
+impl EventKind {
+    pub fn event_type(&self) -> EventType {
+        match self {
+            EventKind::Detection => EventType::Detection,
+            EventKind::Ready => EventType::Ready,
+            EventKind::Fault(_) => EventType::Fault,
+            EventKind::FaultCleared(_) => EventType::FaultCleared,
+            EventKind::SensorAdded => EventType::SensorAdded,
+            EventKind::SensorRemoved => EventType::SensorRemoved,
+            EventKind::SensitivityAdjusted { .. } => EventType::SensitivityAdjusted,
+        }
+    }
+}
+
+// kind of event without its details, for instance for filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventType {
+    Detection,
+    Ready,
+    Fault,
+    FaultCleared,
+    SensorAdded,
+    SensorRemoved,
+    SensitivityAdjusted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaultKind {
+    // output is high for longer than HealthConfig::stuck_high_milisecs
+    StuckHigh,
+    // output was not high for longer than HealthConfig::no_activity_milisecs
+    NoActivity,
+}
+
+impl FaultKind {
+    // name used in logs, JSON output, history database, etc.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FaultKind::StuckHigh => "stuck_high",
+            FaultKind::NoActivity => "no_activity",
+        }
+    }
+}
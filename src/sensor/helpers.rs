@@ -1,5 +1,6 @@
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 
 use crate::sensor::motion::MotionSensor;
@@ -9,7 +10,8 @@ pub async fn process_detections_data(
     sensor: Arc<Vec<Mutex<MotionSensor>>>,
     process: Arc<CancellationToken>,
 ) {
-    let mut detection_data: Vec<(i16, Instant)> = vec![(0, Instant::now()); sensor.len()];
+    // None - sensor was not processed yet, its clock is used to initialize check time
+    let mut detection_data: Vec<Option<(i16, Instant)>> = vec![None; sensor.len()];
     loop {
         if process.is_cancelled() {
             break;
@@ -17,13 +19,15 @@ pub async fn process_detections_data(
 
         let s = sensor.clone();
         for (idx, r) in s.iter().enumerate() {
-            let (last_trigger_count, last_check_time) = detection_data[idx];
             if let Ok(mut locked_resource) = r.try_lock() {
+                let (last_trigger_count, last_check_time) =
+                    *detection_data[idx].get_or_insert_with(|| (0, locked_resource.now()));
+
                 let (tmp_trigger, tmp_time) = locked_resource
                     .process_detections(last_trigger_count, last_check_time)
                     .await;
 
-                detection_data[idx] = (tmp_trigger, tmp_time);
+                detection_data[idx] = Some((tmp_trigger, tmp_time));
             }
         }
         tokio::time::sleep(Duration::from_micros(100)).await;
@@ -49,17 +53,53 @@ pub async fn reading_data_from_sensors(
     }
 }
 
+//
+// tasks started by spawn_detection_threads(), see shutdown()
+//
+#[derive(Debug)]
+pub struct DetectionTasks {
+    sensors: Arc<Vec<Mutex<MotionSensor>>>,
+    stop_command: Arc<CancellationToken>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl DetectionTasks {
+    //
+    // cancels "stop_command" (if it's not cancelled yet) and waits until both tasks are finished,
+    // then every sensor is shut down gracefully - see MotionSensor::shutdown()
+    //
+    pub async fn shutdown(self) {
+        self.stop_command.cancel();
+
+        for task in self.tasks {
+            let _ = task.await;
+        }
+
+        for sensor in self.sensors.iter() {
+            sensor.lock().await.shutdown().await;
+        }
+    }
+}
+
 pub fn spawn_detection_threads(
     sensors: Arc<Vec<Mutex<MotionSensor>>>,
     stop_command: Arc<CancellationToken>,
-) {
+) -> DetectionTasks {
     let sensors_copy = sensors.clone();
 
     let stop_command_copy = stop_command.clone();
-    tokio::spawn(async move { process_detections_data(sensors_copy, stop_command_copy).await });
+    let processing =
+        tokio::spawn(async move { process_detections_data(sensors_copy, stop_command_copy).await });
 
+    let sensors_copy = sensors.clone();
     let stop_command_copy = stop_command.clone();
-    tokio::spawn(async move {
-        reading_data_from_sensors(sensors.clone(), stop_command_copy).await;
+    let reading = tokio::spawn(async move {
+        reading_data_from_sensors(sensors_copy, stop_command_copy).await;
     });
+
+    DetectionTasks {
+        sensors,
+        stop_command,
+        tasks: vec![processing, reading],
+    }
 }
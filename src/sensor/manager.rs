@@ -0,0 +1,350 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{self, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::backend::{GpioInput, InputBackend};
+use super::bus::EventBus;
+use super::config::SensorConfig;
+use super::events::{DetectionEvent, EventKind};
+use super::motion::MotionSensor;
+
+// sensor output is read this many times per refresh period, so short pulses are not missed
+const SAMPLES_PER_REFRESH: u32 = 10;
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagerError {
+    // sensor with this name is already managed
+    DuplicateName(String),
+    // pin is already used by the sensor with the given name
+    DuplicatePin { pin_number: u8, used_by: String },
+    // there is no sensor with this name
+    NotFound(String),
+    // GPIO pin of the new sensor cannot be opened
+    Gpio(String),
+    // settings of the new sensor cannot work together, see SensorConfig::validate()
+    InvalidConfig(String),
+}
+
+impl fmt::Display for ManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagerError::DuplicateName(name) => write!(f, "sensor {name} already exists"),
+            ManagerError::DuplicatePin {
+                pin_number,
+                used_by,
+            } => write!(f, "pin {pin_number} is already used by sensor {used_by}"),
+            ManagerError::NotFound(name) => write!(f, "sensor {name} not found"),
+            ManagerError::Gpio(e) => write!(f, "cannot open GPIO pin: {e}"),
+            ManagerError::InvalidConfig(e) => write!(f, "invalid sensor config: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ManagerError {}
+
+#[derive(Debug)]
+struct ManagedSensor {
+    config: SensorConfig,
+    sensor: Arc<Mutex<MotionSensor>>,
+    stop: CancellationToken,
+    task: JoinHandle<()>,
+    // reads GPIO pin from config, see add_sensor()
+    gpio: bool,
+}
+
+//
+// Runs every sensor in its own task, with its own cadence based on refresh_rate_milisecs.
+// Unlike spawn_detection_threads(), a slow sensor (or a sensor locked by somebody else) doesn't
+// delay readings of other sensors, and sensors can be added or removed at any time.
+//
+// Sensors created by add_sensor() send their detections and events to channels and bus set
+// on the manager. SensorAdded and SensorRemoved events are sent there as well.
+//
+#[derive(Debug)]
+pub struct SensorManager {
+    sensors: Vec<ManagedSensor>,
+    stop: Arc<CancellationToken>,
+    detection_channel: Option<Sender<(String, SystemTime)>>,
+    event_channel: Option<Sender<DetectionEvent>>,
+    event_bus: Option<EventBus>,
+}
+
+impl SensorManager {
+    pub fn new() -> Self {
+        Self::with_cancellation_token(Arc::new(CancellationToken::new()))
+    }
+
+    // every sensor is stopped when "stop" is cancelled
+    pub fn with_cancellation_token(stop: Arc<CancellationToken>) -> Self {
+        Self {
+            sensors: Vec::new(),
+            stop,
+            detection_channel: None,
+            event_channel: None,
+            event_bus: None,
+        }
+    }
+
+    // valid detections of sensors created by add_sensor()
+    pub fn set_detection_channel(&mut self, channel: Sender<(String, SystemTime)>) {
+        self.detection_channel = Some(channel);
+    }
+
+    // events of sensors created by add_sensor() and lifecycle events of all sensors
+    pub fn set_event_channel(&mut self, channel: Sender<DetectionEvent>) {
+        self.event_channel = Some(channel);
+    }
+
+    // the same as set_event_channel(), but events are published to the bus
+    pub fn set_event_bus(&mut self, bus: EventBus) {
+        self.event_bus = Some(bus);
+    }
+
+    //
+    // opens GPIO pin from config and starts the sensor. Only these sensors must have unique pins,
+    // "pin_number" of other sensors is not used.
+    //
+    pub fn add_sensor(&mut self, config: SensorConfig) -> Result<(), ManagerError> {
+        self.check_new(&config)?;
+        self.check_pin(&config)?;
+
+        let input = GpioInput::open(config.pin_number)
+            .map_err(|e| ManagerError::Gpio(format!("sensor {}: {e}", config.name)))?;
+
+        let sensor = self.sensor_with_input(config, Box::new(input));
+        self.start(sensor, true);
+        Ok(())
+    }
+
+    // starts the sensor which reads its output from any backend, see MotionSensor::with_input()
+    pub fn add_sensor_with_input(
+        &mut self,
+        config: SensorConfig,
+        input: Box<dyn InputBackend>,
+    ) -> Result<(), ManagerError> {
+        self.check_new(&config)?;
+
+        let sensor = self.sensor_with_input(config, input);
+        self.start(sensor, false);
+        Ok(())
+    }
+
+    // starts already created sensor, its channels and bus are not changed
+    pub fn add(&mut self, sensor: MotionSensor) -> Result<(), ManagerError> {
+        self.check_new(&sensor.config)?;
+
+        self.start(sensor, false);
+        Ok(())
+    }
+
+    // sensor with channels and bus of the manager
+    fn sensor_with_input(
+        &self,
+        config: SensorConfig,
+        input: Box<dyn InputBackend>,
+    ) -> MotionSensor {
+        // detections are not sent anywhere when manager has no detection channel
+        let detection_channel = self
+            .detection_channel
+            .clone()
+            .unwrap_or_else(|| mpsc::channel(1).0);
+
+        let mut sensor = MotionSensor::with_input(config, detection_channel, input);
+        if let Some(channel) = self.event_channel.as_ref() {
+            sensor.set_event_channel(channel.clone());
+        }
+        if let Some(bus) = self.event_bus.as_ref() {
+            sensor.set_event_bus(bus.clone());
+        }
+        sensor
+    }
+
+    fn start(&mut self, sensor: MotionSensor, gpio: bool) {
+        let config = sensor.config.clone();
+        let sensor = Arc::new(Mutex::new(sensor));
+        let stop = self.stop.child_token();
+        let task = tokio::spawn(sensor_task(sensor.clone(), stop.clone()));
+
+        info!(sensor = %config.name, "sensor {} added", config.name);
+        self.send_event(&config.name, EventKind::SensorAdded);
+        self.sensors.push(ManagedSensor {
+            config,
+            sensor,
+            stop,
+            task,
+            gpio,
+        });
+    }
+
+    // stops the sensor gracefully, see stop()
+    pub async fn remove_sensor(&mut self, name: &str) -> Result<(), ManagerError> {
+        let Some(idx) = self.sensors.iter().position(|s| s.config.name == name) else {
+            return Err(ManagerError::NotFound(name.to_string()));
+        };
+
+        let managed = self.sensors.remove(idx);
+        managed.stop.cancel();
+        self.shut_down(managed).await;
+
+        info!(sensor = %name, "sensor {name} removed");
+        Ok(())
+    }
+
+    // configs of sensors in order of adding
+    pub fn list_sensors(&self) -> Vec<SensorConfig> {
+        self.sensors.iter().map(|s| s.config.clone()).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sensors.iter().any(|s| s.config.name == name)
+    }
+
+    // names of sensors in order of adding
+    pub fn names(&self) -> Vec<String> {
+        self.sensors.iter().map(|s| s.config.name.clone()).collect()
+    }
+
+    pub fn sensor(&self, name: &str) -> Option<Arc<Mutex<MotionSensor>>> {
+        self.sensors
+            .iter()
+            .find(|s| s.config.name == name)
+            .map(|s| s.sensor.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sensors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty()
+    }
+
+    //
+    // graceful stop of every sensor: it's finished when all sensor tasks are stopped, valid
+    // detections of readings which were still waiting for processing are sent and GPIO pins are
+    // reset to their original mode (see MotionSensor::shutdown()). SensorRemoved event is the
+    // last event of each sensor.
+    //
+    pub async fn stop(&mut self) {
+        self.stop.cancel();
+
+        for managed in std::mem::take(&mut self.sensors) {
+            let name = managed.config.name.clone();
+            self.shut_down(managed).await;
+            info!(sensor = %name, "sensor {name} stopped");
+        }
+    }
+
+    // task of the sensor must be already cancelled
+    async fn shut_down(&self, managed: ManagedSensor) {
+        let _ = managed.task.await;
+        managed.sensor.lock().await.shutdown().await;
+        drop(managed.sensor);
+
+        self.send_event(&managed.config.name, EventKind::SensorRemoved);
+    }
+
+    fn check_new(&self, config: &SensorConfig) -> Result<(), ManagerError> {
+        config
+            .validate()
+            .map_err(|e| ManagerError::InvalidConfig(format!("sensor {}: {e}", config.name)))?;
+
+        for managed in self.sensors.iter() {
+            if managed.config.name == config.name {
+                return Err(ManagerError::DuplicateName(config.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_pin(&self, config: &SensorConfig) -> Result<(), ManagerError> {
+        let used_by = self
+            .sensors
+            .iter()
+            .find(|managed| managed.gpio && managed.config.pin_number == config.pin_number);
+
+        match used_by {
+            Some(managed) => Err(ManagerError::DuplicatePin {
+                pin_number: config.pin_number,
+                used_by: managed.config.name.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn send_event(&self, sensor_name: &str, kind: EventKind) {
+        let event = DetectionEvent {
+            sensor_name: sensor_name.to_string(),
+            time: SystemTime::now(),
+            kind,
+        };
+
+        if let Some(event_bus) = self.event_bus.as_ref() {
+            event_bus.publish(event.clone());
+        }
+        if let Some(event_channel) = self.event_channel.as_ref()
+            && let Err(e) = event_channel.try_send(event)
+        {
+            warn!(
+                sensor = %sensor_name,
+                "event channel is full or closed, {:?} event of sensor {sensor_name} dropped",
+                e.into_inner().kind
+            );
+        }
+    }
+}
+
+impl Default for SensorManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//
+// dropped manager cancels tasks of its sensors, so they don't run (and keep their GPIO pins)
+// forever. It's not a graceful stop - readings waiting for processing are lost and there are no
+// SensorRemoved events, use stop() for that. "stop" token given to with_cancellation_token()
+// is not cancelled, only child tokens of sensors.
+//
+impl Drop for SensorManager {
+    fn drop(&mut self) {
+        for managed in self.sensors.iter() {
+            managed.stop.cancel();
+        }
+    }
+}
+
+async fn sensor_task(sensor: Arc<Mutex<MotionSensor>>, stop: CancellationToken) {
+    let (refresh_rate, mut last_check_time) = {
+        let locked = sensor.lock().await;
+        (locked.config.refresh_rate_milisecs, locked.now())
+    };
+
+    let sample_interval =
+        (Duration::from_millis(refresh_rate) / SAMPLES_PER_REFRESH).max(MIN_SAMPLE_INTERVAL);
+    let mut interval = tokio::time::interval(sample_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut trigger_count = 0;
+
+    loop {
+        tokio::select! {
+            _ = stop.cancelled() => break,
+            _ = interval.tick() => {
+                let mut locked = sensor.lock().await;
+                locked.reading_from_sensor().await;
+                (trigger_count, last_check_time) = locked
+                    .process_detections(trigger_count, last_check_time)
+                    .await;
+            }
+        }
+    }
+}
@@ -1,3 +1,30 @@
+#[cfg(feature = "std")]
+pub mod adaptive;
+#[cfg(feature = "std")]
+pub mod backend;
+#[cfg(feature = "tokio")]
+pub mod bus;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
 pub mod config;
+pub mod detector;
+pub mod embedded;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "tokio")]
 pub mod helpers;
+#[cfg(feature = "tokio")]
+pub mod manager;
+#[cfg(feature = "tokio")]
 pub mod motion;
+#[cfg(feature = "std")]
+pub mod simulation;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "tokio")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "tokio")]
+pub mod tuning;
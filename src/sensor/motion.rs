@@ -1,12 +1,21 @@
-use log::info;
+use rppal::gpio::Gpio;
 use rppal::gpio::Mode::Input;
-use rppal::gpio::{Gpio, IoPin};
-use std::time::{Instant, SystemTime};
-use tokio::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::Sender;
+use tracing::{Span, debug, info, info_span, trace, warn};
 
 const DETECTION_MARGIN_MILISECS: u64 = 10;
 
-use super::config::SensorConfig;
+use super::adaptive::Adaptation;
+use super::backend::{GpioInput, InputBackend};
+use super::bus::EventBus;
+use super::clock::{Clock, TokioClock};
+use super::config::{ActiveLevel, Bias, SensorConfig};
+use super::detector::{CoreEvent, DetectionCore, DetectorConfig, OverflowPolicy};
+use super::events::{DetectionEvent, EventKind, FaultKind};
+use super::stats::{SensorStats, StatsCounters};
+use super::trace::{SensorRecorder, TraceRecorder};
 
 #[derive(Debug)]
 pub struct MotionSensor {
@@ -27,9 +36,37 @@ pub struct SensorAdditionalSettings {
     pub sensor_test_data: Option<Vec<u64>>,
     pub sensor_test_time: Option<Instant>,
     pub sensor_test_index: usize,
-    pub pin: Option<IoPin>,
-    pub detection_stream_channel: Option<Sender<bool>>,
-    pub detections_receiver: Receiver<bool>,
+    // source of sensor output state: GPIO pin, recorded trace, etc. None only for sensor_test_data
+    pub input: Option<Box<dyn InputBackend>>,
+    // when set, every change of sensor output state is written to a trace file
+    pub recorder: Option<SensorRecorder>,
+    // detection logic, high readings wait there for processing
+    pub core: DetectionCore,
+    // every time measurement of the sensor goes through this clock, see set_clock()
+    pub clock: Arc<dyn Clock>,
+    // all events of the sensor (valid detections, faults, etc.), see set_event_channel()
+    pub event_channel: Option<Sender<DetectionEvent>>,
+    // all events of the sensor are published to this bus as well, see set_event_bus()
+    pub event_bus: Option<EventBus>,
+    pub health: HealthState,
+    // time of the detection core is counted from this moment (so warm-up period as well)
+    pub started_at: Instant,
+    // see stats()
+    pub stats: StatsCounters,
+    // adaptive sensitivity, when SensorConfig::adaptive is set
+    pub adaptation: Option<Adaptation>,
+    // everything the sensor logs is within this span, so subscribers can tell sensors apart
+    pub span: Span,
+}
+
+// state of health supervision, see HealthConfig
+#[derive(Debug, Default)]
+pub struct HealthState {
+    // since when sensor output is high, None when it's low
+    pub high_since: Option<Instant>,
+    // last time when sensor output was high (or the first reading of the sensor)
+    pub last_activity: Option<Instant>,
+    pub active_faults: Vec<FaultKind>,
 }
 
 impl MotionSensor {
@@ -48,50 +85,96 @@ impl MotionSensor {
             refresh_rate_milisecs: sensor_refresh_rate_milisecs,
             motion_time_period_milisecs: sensor_motion_time_period_milisecs,
             minimal_triggering_number: sensor_minimal_triggering_number,
+            ..Default::default()
         };
 
         // default values
-        let mut pin_init = None;
-        let detection_stream_channel_init;
+        let mut input_init: Option<Box<dyn InputBackend>> = None;
 
         if sensor_test_data.is_none() {
             let gpio = Gpio::new().unwrap();
-            let pin: IoPin;
-            loop {
-                pin = match gpio.get(sensor_pin_number) {
-                    Ok(p) => p.into_io(Input),
+            let pin = loop {
+                match gpio.get(sensor_pin_number) {
+                    Ok(p) => break p.into_io(Input),
                     Err(_) => {
                         continue;
                     }
                 };
-                break;
-            }
+            };
 
-            pin_init = Some(pin);
+            input_init = Some(Box::new(GpioInput::new(pin)));
         }
 
-        let (detections_stream, detections_receiver) = mpsc::channel(10);
+        Self::init(
+            config,
+            sensor_transmission_channel,
+            sensor_test_data,
+            input_init,
+        )
+    }
 
-        detection_stream_channel_init = Some(detections_stream);
+    //
+    // sensor which reads its output state from any backend instead of GPIO pin, for instance
+    // ReplayInput with recorded trace. "pin_number" from config is not used in this case.
+    //
+    pub fn with_input(
+        config: SensorConfig,
+        sensor_transmission_channel: Sender<(String, SystemTime)>,
+        input: Box<dyn InputBackend>,
+    ) -> Self {
+        Self::init(config, sensor_transmission_channel, None, Some(input))
+    }
 
+    fn init(
+        config: SensorConfig,
+        sensor_transmission_channel: Sender<(String, SystemTime)>,
+        sensor_test_data: Option<Vec<u64>>,
+        mut input: Option<Box<dyn InputBackend>>,
+    ) -> Self {
         //
         // initialization
         //
-        info!("Starting sensor: {:#?}", config.name);
+        let span = info_span!("sensor", name = %config.name);
+        span.in_scope(|| info!("Starting sensor: {:#?}", config.name));
 
         // by default it's None when sensor is initialized
         // for testing it will be initialized with current time
         let sensor_test_time = None;
         let sensor_test_index = 0;
 
+        // without bias in config the pin keeps whatever was configured outside of the crate
+        if config.bias != Bias::None
+            && let Some(input) = input.as_mut()
+        {
+            input.set_bias(config.bias);
+        }
+
+        let clock: Arc<dyn Clock> = Arc::new(TokioClock::new());
+
+        let mut core = DetectionCore::new(DetectorConfig::from(&config));
+        let adaptation = config.adaptive.map(|adaptive| {
+            let adaptation =
+                Adaptation::new(adaptive, config.minimal_triggering_number, clock.now());
+            core.set_minimal_triggering_number(adaptation.current());
+            adaptation
+        });
+
         let additional_settings = SensorAdditionalSettings {
             stop: false,
             sensor_test_data,
             sensor_test_time,
             sensor_test_index,
-            pin: pin_init,
-            detection_stream_channel: detection_stream_channel_init,
-            detections_receiver,
+            input,
+            recorder: None,
+            core,
+            started_at: clock.now(),
+            clock,
+            event_channel: None,
+            event_bus: None,
+            health: HealthState::default(),
+            stats: StatsCounters::default(),
+            adaptation,
+            span,
         };
 
         Self {
@@ -103,32 +186,182 @@ impl MotionSensor {
         }
     }
 
-    pub async fn reading_from_sensor(&mut self) {
-        //
-        let detection_stream_channel = self.additional_settings.detection_stream_channel.clone();
+    // replaces default clock (which follows tokio time) - for instance with ManualClock in tests
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.additional_settings.started_at = clock.now();
+        if let Some(adaptation) = self.additional_settings.adaptation.as_mut() {
+            adaptation.restart(clock.now());
+        }
+        self.additional_settings.clock = clock;
+    }
+
+    // current time of the sensor clock
+    pub fn now(&self) -> Instant {
+        self.additional_settings.clock.now()
+    }
+
+    // every event of the sensor is sent to this channel, including valid detections which are
+    // sent to detection_channel as well
+    pub fn set_event_channel(&mut self, channel: Sender<DetectionEvent>) {
+        self.additional_settings.event_channel = Some(channel);
+    }
+
+    // every event of the sensor is published to this bus, so many subscribers can receive it
+    pub fn set_event_bus(&mut self, bus: EventBus) {
+        self.additional_settings.event_bus = Some(bus);
+    }
+
+    pub fn active_faults(&self) -> &[FaultKind] {
+        &self.additional_settings.health.active_faults
+    }
+
+    pub fn is_warming_up(&self) -> bool {
+        self.additional_settings
+            .core
+            .is_warming_up(self.core_time(self.now()))
+    }
+
+    // triggers read during warm-up period, they are never validated
+    pub fn warmup_triggers(&self) -> u64 {
+        self.additional_settings.core.warmup_triggers()
+    }
+
+    // counters of readings and detections since the sensor was created
+    pub fn stats(&self) -> SensorStats {
+        let counters = &self.additional_settings.stats;
+        let core = &self.additional_settings.core;
+
+        SensorStats {
+            raw_highs: counters.raw_highs,
+            pre_detections: core.pre_detections(),
+            valid_detections: counters.valid_detections,
+            suppressed: counters.quarantined + core.warmup_triggers(),
+            dropped_readings: core.dropped_readings(),
+            coalesced_readings: core.coalesced_readings(),
+            blocked_readings: counters.blocked_readings,
+            dropped_sends: counters.dropped_sends,
+            dropped_events: counters.dropped_events,
+            first_detection_time: counters.first_detection_time,
+            last_detection_time: self.last_detection_time,
+            last_any_detection_time: self.last_any_detection_time,
+            detections_last_minute: counters.detections_within_window(self.now()),
+        }
+    }
+
+    // effective minimal_triggering_number, it differs from config when adaptive sensitivity is on
+    pub fn minimal_triggering_number(&self) -> i16 {
+        self.additional_settings
+            .core
+            .config()
+            .minimal_triggering_number
+    }
+
+    fn adapt_sensitivity(&mut self, now: Instant, triggers: u64, valid_detection: bool) {
+        let from = self.minimal_triggering_number();
+        let Some(to) = self
+            .additional_settings
+            .adaptation
+            .as_mut()
+            .and_then(|adaptation| adaptation.update(now, triggers, valid_detection))
+        else {
+            return;
+        };
+
+        info!(
+            "sensor {}: minimal triggering number adjusted from {from} to {to}",
+            self.config.name
+        );
+        self.additional_settings
+            .core
+            .set_minimal_triggering_number(to);
+        self.send_event(
+            EventKind::SensitivityAdjusted { from, to },
+            self.additional_settings.clock.system_now(),
+        );
+    }
+
+    // high reading of sensor output goes to the detection core, unless sensor is quarantined
+    fn sample_high(&mut self, now: Instant) {
+        trace!("sensor {}: output high", self.config.name);
+        self.last_any_detection_time = Some(now);
+        self.additional_settings.stats.raw_highs += 1;
+
+        if self.is_quarantined() {
+            debug!("sensor {}: quarantined, reading ignored", self.config.name);
+            self.additional_settings.stats.quarantined += 1;
+            return;
+        }
+
+        // when there are too many pending readings, overflow policy from config decides which
+        // one is dropped
+        let core_now = self.core_time(now);
+        if !self.additional_settings.core.sample(core_now, true) {
+            debug!(
+                "sensor {}: too many readings waiting for processing, reading dropped",
+                self.config.name
+            );
+        }
+    }
+
+    // time of the detection core for the given moment of the sensor clock
+    fn core_time(&self, at: Instant) -> Duration {
+        at.saturating_duration_since(self.additional_settings.started_at)
+    }
+
+    // quarantined sensor doesn't produce valid detections, see HealthConfig::quarantine
+    pub fn is_quarantined(&self) -> bool {
+        self.config.health.quarantine && !self.additional_settings.health.active_faults.is_empty()
+    }
+
+    // records every change of sensor output state, see sensor::trace module for file format
+    pub fn record_trace(&mut self, recorder: TraceRecorder) {
+        self.additional_settings.recorder = Some(SensorRecorder::new(recorder));
+    }
+
+    //
+    // reads sensor output once, returns true when it was high (or when test detection was
+    // generated) - it's informational only, for instance for diagnostic tools
+    //
+    pub async fn reading_from_sensor(&mut self) -> bool {
+        let _span = self.additional_settings.span.clone().entered();
+        let mut level = false;
+
+        // like a blocking send - sensor output is not read until there is room for a reading
+        if self.config.overflow_policy == OverflowPolicy::Block
+            && self.additional_settings.core.is_full()
+        {
+            self.additional_settings.stats.blocked_readings += 1;
+            return level;
+        }
 
         //
-        // BEGIN: real detections from GPIO
+        // BEGIN: real detections from GPIO (or other backend pretending to be GPIO pin)
         //
-        if self.additional_settings.sensor_test_data.is_none() {
-            let pin = self.additional_settings.pin.as_mut().expect(
-                "sensor not initialized - this method should be called AFTER start_detector()",
-            );
-            //
-            if pin.is_high() {
-                // try to send as many as possible but if the channel is full, we just ignore it
-                // that's why try_send() is used here
-                // unwrap_or_default() - because we don't care if each single detection is successfully
-                //                       sent
-                if let Some(detection_stream_channel) = detection_stream_channel.as_ref() {
-                    detection_stream_channel
-                        .try_send(true)
-                        .unwrap_or_default()
-                }
+        let now = self.now();
+        if let Some(is_high) = self
+            .additional_settings
+            .input
+            .as_mut()
+            .map(|input| input.is_high(now))
+        {
+            if let Some(recorder) = self.additional_settings.recorder.as_mut()
+                && let Err(e) = recorder.sample(&self.config.name, now, is_high)
+            {
+                warn!("cannot record trace of sensor {}: {e}", self.config.name);
+            }
+
+            // recorded trace keeps physical levels, so it's replayed the same way
+            let is_active = is_high != (self.config.active_level == ActiveLevel::Low);
+            level = is_active;
+
+            self.supervise_health(now, is_active);
+
+            if is_active {
+                self.sample_high(now);
             }
         }
         //
-        // END: real detections from GPIO
+        // END: real detections from GPIO (or other backend pretending to be GPIO pin)
         //
 
         //
@@ -138,7 +371,7 @@ impl MotionSensor {
         if self.additional_settings.sensor_test_data.is_some() {
             if self.additional_settings.sensor_test_time.is_none() {
                 // starting internal timer which will be used as a reference for testing
-                self.additional_settings.sensor_test_time = Some(Instant::now());
+                self.additional_settings.sensor_test_time = Some(self.now());
             }
 
             let detections_time_list = self.additional_settings.sensor_test_data.clone().unwrap();
@@ -148,10 +381,8 @@ impl MotionSensor {
 
             if current_index < detections_time_list_length {
                 let milisecs_now = self
-                    .additional_settings
-                    .sensor_test_time
-                    .unwrap()
-                    .elapsed()
+                    .now()
+                    .saturating_duration_since(self.additional_settings.sensor_test_time.unwrap())
                     .as_millis() as u64;
 
                 // taking detection time from the list
@@ -167,20 +398,24 @@ impl MotionSensor {
                 {
                     // updating index - next time we will take next detection from the list
                     self.additional_settings.sensor_test_index += 1;
-
-                    // sending testing detection to the channel which looks like "real"
-                    if let Some(detection_channel) = detection_stream_channel {
-                        detection_channel
-                            .try_send(true)
-                            .expect("cannot use channel for detection stream");
-                    }
+                    level = true;
                 }
             }
+
+            let now = self.now();
+            self.supervise_health(now, level);
+
+            // testing detection is processed exactly like "real" one
+            if level {
+                self.sample_high(now);
+            }
         }
         //
         // END: testing detections logic: we take detections from Vec<u64> - each such detection
         //      invokes same actions as normal GPIO pin
         //
+
+        level
     }
 
     //
@@ -191,63 +426,203 @@ impl MotionSensor {
         last_sensor_trigger_count: i16,
         last_check_time: Instant,
     ) -> (i16, Instant) {
-        let mut sensor_trigger_count = last_sensor_trigger_count;
+        let _span = self.additional_settings.span.clone().entered();
+        let now = self.now();
+        let core_now = self.core_time(now);
+        let core_last_check = self.core_time(last_check_time);
 
-        if last_check_time.elapsed().as_millis() as u64 <= self.config.refresh_rate_milisecs {
-            // "sensor refresh rate" - if it's too early to check, then we return instantly
-            // but we don't modify "last_check_time" - in another function await this time still
-            // will be used to determine if it's time to check internal channel for detections
+        //
+        // the counter and time of the last check are kept by the caller, detection core
+        // continues from them. It processes at most one reading per "sensor refresh rate" - in
+        // that sense, short "refresh_rate_milisec" values just means we process readings more
+        // often and we can count more detections.
+        //
+        let core = &mut self.additional_settings.core;
+        core.set_trigger_state(last_sensor_trigger_count, core_last_check);
+        let pre_detections = core.pre_detections();
+        let warmup_triggers = core.warmup_triggers();
+        let event = core.poll(core_now);
+        let checked = core.last_check() != core_last_check;
+        let sensor_trigger_count = core.trigger_count();
+
+        if core.warmup_triggers() != warmup_triggers {
+            debug!(
+                "sensor {}: trigger ignored during warm-up",
+                self.config.name
+            );
+        } else if core.pre_detections() != pre_detections && event.is_none() {
+            debug!(
+                count = sensor_trigger_count,
+                min = core.config().minimal_triggering_number,
+                "sensor {}: pre-detection",
+                self.config.name
+            );
+        }
+
+        self.adapt_sensitivity(
+            now,
+            self.additional_settings.core.pre_detections() - pre_detections,
+            event == Some(CoreEvent::Detection),
+        );
+
+        self.handle_core_event(event, now);
+
+        if !checked {
+            // "sensor refresh rate" - it was too early to check, so we don't modify
+            // "last_check_time" - next time it still will be used to determine if it's time
+            // to process pending readings
             return (last_sensor_trigger_count, last_check_time);
         }
 
-        // sensor refresh rate is larger or equal the actual timer - now we can read state of the
-        // channel and proceed with the logic. In that sense, short "refresh_rate_milisec" values
-        // just means we read detection channel more often and we can count more detections.
+        // return current counter and time which later will be used to determine another detections (valid or pre-detections)
+        (sensor_trigger_count, now)
+    }
 
-        //
-        // reading detections from channel - these detections may come from real gpio
-        // pin or from tests without gpio involved
-        //
-        // try_recv() because this is an async func - we don't care if there is no detection data
-        // in the channel and moving forward asap. If there are detections in the channel then we
-        // will proceed them normally, but the highest priority of this function is to don't block it.
-        //
-        if self
-            .additional_settings
-            .detections_receiver
-            .try_recv()
-            .is_ok()
-        {
-            // this func is async so we increment counter (or not)
-            sensor_trigger_count += 1;
+    //
+    // graceful stop: readings still waiting for processing are processed right away, so their
+    // valid detections are not lost. Then trace is flushed and input is released - GPIO pin is
+    // reset to the mode it had before the sensor was started. Nothing is read after it.
+    //
+    pub async fn shutdown(&mut self) {
+        let _span = self.additional_settings.span.clone().entered();
+        let now = self.now();
 
-            // because we use Instant::now, the real time difference needs to be multiply by counts to
-            // reflect real motion time period time
-            let time_difference = self.config.refresh_rate_milisecs * sensor_trigger_count as u64;
+        while self.additional_settings.core.pending() > 0 {
+            let event = self.additional_settings.core.flush();
+            self.handle_core_event(event, now);
+        }
 
-            if time_difference > self.config.motion_time_period_milisecs {
-                // this is a new detection - reset counter
-                sensor_trigger_count = 1;
-            }
+        self.additional_settings.recorder = None;
+        self.additional_settings.input = None;
+        self.additional_settings.sensor_test_data = None;
+
+        info!("sensor {}: shut down", self.config.name);
+    }
 
-            if sensor_trigger_count >= self.config.minimal_triggering_number {
+    fn handle_core_event(&mut self, event: Option<CoreEvent>, now: Instant) {
+        match event {
+            Some(CoreEvent::Ready) => {
+                info!(
+                    "sensor {}: warm-up finished, {} trigger(s) ignored",
+                    self.config.name,
+                    self.warmup_triggers()
+                );
+                self.send_event(
+                    EventKind::Ready,
+                    self.additional_settings.clock.system_now(),
+                );
+            }
+            Some(CoreEvent::Detection) => {
                 //
                 // minimal_triggering_number is reached - this is valid detection so send it to the main channel
                 //
-                let t = SystemTime::now();
+                let t = self.additional_settings.clock.system_now();
                 self.last_detection_time = Some(t);
+                info!("sensor {}: valid detection", self.config.name);
 
                 // sending real (VALID) detection to the main channel as we reached suitable "minimal_triggering_number"
-                self.detection_channel
+                if self
+                    .detection_channel
                     .try_send((self.config.name.clone(), t))
-                    .unwrap_or_default();
+                    .is_err()
+                {
+                    warn!(
+                        "sensor {}: detection channel is full or closed, valid detection dropped",
+                        self.config.name
+                    );
+                    self.additional_settings.stats.dropped_sends += 1;
+                }
+                self.additional_settings.stats.detection(now, t);
+                self.send_event(EventKind::Detection, t);
+            }
+            None => {}
+        }
+    }
+
+    fn send_event(&mut self, kind: EventKind, time: SystemTime) {
+        let _span = self.additional_settings.span.clone().entered();
+        let event = DetectionEvent {
+            sensor_name: self.config.name.clone(),
+            time,
+            kind,
+        };
+
+        if let Some(event_bus) = self.additional_settings.event_bus.as_ref() {
+            event_bus.publish(event.clone());
+        }
+
+        if let Some(event_channel) = self.additional_settings.event_channel.as_ref()
+            && let Err(e) = event_channel.try_send(event)
+        {
+            warn!(
+                "sensor {}: event channel is full or closed, {:?} event dropped",
+                self.config.name,
+                e.into_inner().kind
+            );
+            self.additional_settings.stats.dropped_events += 1;
+        }
+    }
+
+    //
+    // checks fault conditions from HealthConfig after each reading of sensor output
+    //
+    fn supervise_health(&mut self, now: Instant, is_high: bool) {
+        let health = &mut self.additional_settings.health;
+
+        if is_high {
+            health.high_since.get_or_insert(now);
+            health.last_activity = Some(now);
+        } else {
+            health.high_since = None;
+        }
+        let last_activity = *health.last_activity.get_or_insert(now);
 
-                // reset counter - next detection will be counted as different one from zero again
-                sensor_trigger_count = 0;
+        let stuck_high = match (self.config.health.stuck_high_milisecs, health.high_since) {
+            (Some(limit), Some(high_since)) => {
+                now.saturating_duration_since(high_since) > Duration::from_millis(limit)
             }
+            _ => false,
+        };
+
+        let no_activity = self
+            .config
+            .health
+            .no_activity_milisecs
+            .is_some_and(|limit| {
+                now.saturating_duration_since(last_activity) > Duration::from_millis(limit)
+            });
+
+        self.update_fault(FaultKind::StuckHigh, stuck_high);
+        self.update_fault(FaultKind::NoActivity, no_activity);
+    }
+
+    fn update_fault(&mut self, fault: FaultKind, active: bool) {
+        let active_faults = &mut self.additional_settings.health.active_faults;
+
+        if active == active_faults.contains(&fault) {
+            return;
         }
 
-        // return current counter and time which later will be used to determine another detections (valid or pre-detections)
-        (sensor_trigger_count, Instant::now())
+        let kind = if active {
+            warn!("sensor {}: fault {}", self.config.name, fault.as_str());
+            active_faults.push(fault);
+
+            if self.config.health.quarantine {
+                // triggers collected before the fault are not valid anymore
+                self.additional_settings.core.clear_pending();
+            }
+
+            EventKind::Fault(fault)
+        } else {
+            info!(
+                "sensor {}: fault {} cleared",
+                self.config.name,
+                fault.as_str()
+            );
+            active_faults.retain(|f| *f != fault);
+            EventKind::FaultCleared(fault)
+        };
+
+        self.send_event(kind, self.additional_settings.clock.system_now());
     }
 }
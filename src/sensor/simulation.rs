@@ -0,0 +1,286 @@
+use serde::Deserialize;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::backend::InputBackend;
+
+//
+// Scenario of a simulated installation - people walking through rooms, random noise and faults
+// of sensors. Every sensor of the scenario gets its own SimulatedInput, so many virtual sensors
+// can be run by SensorManager (or pir-motiond, see its [simulation] section) without hardware.
+// Times are counted from the first reading of any sensor of the simulation.
+//
+//   seed = 7
+//
+//   [[walks]]                    # hall -> kitchen -> garden, every 2 minutes
+//   sensors = ["Hall", "Kitchen", "Garden"]
+//   start_milisecs = 5000
+//   dwell_milisecs = 3000
+//   gap_milisecs = 1000
+//   every_milisecs = 120000
+//
+//   [[noise]]                    # all sensors, 2 random short triggers per minute on average
+//   rate_per_minute = 2.0
+//
+//   [[stuck_high]]
+//   sensor = "Garden"
+//   from_milisecs = 60000
+//
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Scenario {
+    // the same seed gives the same noise, so simulations can be repeated
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub walks: Vec<Walk>,
+    #[serde(default)]
+    pub noise: Vec<Noise>,
+    #[serde(default)]
+    pub stuck_high: Vec<StuckHigh>,
+}
+
+// person walking in front of sensors one after another
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Walk {
+    pub sensors: Vec<String>,
+    #[serde(default)]
+    pub start_milisecs: u64,
+    // output of each sensor is high for this time
+    pub dwell_milisecs: u64,
+    // time between leaving one sensor and reaching the next one
+    #[serde(default)]
+    pub gap_milisecs: u64,
+    // the walk starts again after this time, None - only once
+    #[serde(default)]
+    pub every_milisecs: Option<u64>,
+}
+
+// short random triggers (insects, heat, interference) - Poisson process with the given rate
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Noise {
+    // empty - every sensor of the simulation
+    #[serde(default)]
+    pub sensors: Vec<String>,
+    pub rate_per_minute: f64,
+    #[serde(default = "default_pulse_milisecs")]
+    pub pulse_milisecs: u64,
+}
+
+fn default_pulse_milisecs() -> u64 {
+    200
+}
+
+// output of the sensor is high all the time - broken sensor or wiring
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StuckHigh {
+    pub sensor: String,
+    #[serde(default)]
+    pub from_milisecs: u64,
+    // None - until the end of simulation
+    #[serde(default)]
+    pub to_milisecs: Option<u64>,
+}
+
+impl Scenario {
+    // every sensor name used by the scenario must be in "sensors"
+    pub fn validate(&self, sensors: &[String]) -> Result<(), String> {
+        let names = self
+            .walks
+            .iter()
+            .flat_map(|w| w.sensors.iter())
+            .chain(self.noise.iter().flat_map(|n| n.sensors.iter()))
+            .chain(self.stuck_high.iter().map(|s| &s.sensor));
+
+        for name in names {
+            if !sensors.contains(name) {
+                return Err(format!("simulation: unknown sensor {name}"));
+            }
+        }
+
+        for walk in self.walks.iter() {
+            if walk.every_milisecs == Some(0) {
+                return Err(String::from(
+                    "simulation: every_milisecs of walk must be greater than 0",
+                ));
+            }
+        }
+
+        for noise in self.noise.iter() {
+            if !noise.rate_per_minute.is_finite() || noise.rate_per_minute < 0.0 {
+                return Err(String::from(
+                    "simulation: rate_per_minute of noise must be a positive number",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    scenario: Arc<Scenario>,
+    // the first reading of any sensor
+    started_at: Arc<OnceLock<Instant>>,
+}
+
+impl Simulation {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            scenario: Arc::new(scenario),
+            started_at: Arc::new(OnceLock::new()),
+        }
+    }
+
+    // backend of a single virtual sensor, see MotionSensor::with_input()
+    pub fn input(&self, sensor_name: &str) -> SimulatedInput {
+        let noise = self
+            .scenario
+            .noise
+            .iter()
+            .filter(|n| n.sensors.is_empty() || n.sensors.iter().any(|s| s == sensor_name))
+            .enumerate()
+            .map(|(idx, noise)| {
+                let seed = self.scenario.seed ^ name_hash(sensor_name) ^ idx as u64;
+                NoiseSource::new(noise, seed)
+            })
+            .collect();
+
+        SimulatedInput {
+            sensor_name: sensor_name.to_string(),
+            scenario: self.scenario.clone(),
+            started_at: self.started_at.clone(),
+            noise,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SimulatedInput {
+    sensor_name: String,
+    scenario: Arc<Scenario>,
+    started_at: Arc<OnceLock<Instant>>,
+    noise: Vec<NoiseSource>,
+}
+
+impl SimulatedInput {
+    fn is_walked(&self, offset: u64) -> bool {
+        self.scenario.walks.iter().any(|walk| {
+            walk.sensors
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| **name == self.sensor_name)
+                .any(|(idx, _)| {
+                    let delay = walk.start_milisecs
+                        + idx as u64 * (walk.dwell_milisecs + walk.gap_milisecs);
+                    let Some(since) = offset.checked_sub(delay) else {
+                        return false;
+                    };
+                    match walk.every_milisecs {
+                        // dwell longer than "every" - output is high all the time
+                        Some(every) => since % every < walk.dwell_milisecs,
+                        None => since < walk.dwell_milisecs,
+                    }
+                })
+        })
+    }
+
+    fn is_stuck(&self, offset: u64) -> bool {
+        self.scenario.stuck_high.iter().any(|stuck| {
+            stuck.sensor == self.sensor_name
+                && offset >= stuck.from_milisecs
+                && stuck.to_milisecs.is_none_or(|to| offset < to)
+        })
+    }
+}
+
+impl InputBackend for SimulatedInput {
+    fn is_high(&mut self, now: Instant) -> bool {
+        let started_at = *self.started_at.get_or_init(|| now);
+        let offset = now.saturating_duration_since(started_at);
+        let milisecs = offset.as_millis() as u64;
+
+        // every noise source is advanced, so its pulses don't depend on other sources
+        let mut noise = false;
+        for source in self.noise.iter_mut() {
+            noise |= source.is_high(offset);
+        }
+
+        noise || self.is_walked(milisecs) || self.is_stuck(milisecs)
+    }
+}
+
+// pulses of a single Noise entry for a single sensor
+#[derive(Debug)]
+struct NoiseSource {
+    rng: SplitMix64,
+    // average time between pulses, None - no pulses at all
+    mean_interval: Option<f64>,
+    pulse: Duration,
+    next_pulse: Duration,
+    high_until: Duration,
+}
+
+impl NoiseSource {
+    fn new(noise: &Noise, seed: u64) -> Self {
+        let mean_interval = (noise.rate_per_minute > 0.0).then(|| 60.0 / noise.rate_per_minute);
+
+        let mut source = Self {
+            rng: SplitMix64(seed),
+            mean_interval,
+            pulse: Duration::from_millis(noise.pulse_milisecs),
+            next_pulse: Duration::ZERO,
+            high_until: Duration::ZERO,
+        };
+        source.next_pulse = source.interval();
+        source
+    }
+
+    fn is_high(&mut self, offset: Duration) -> bool {
+        if self.mean_interval.is_none() {
+            return false;
+        }
+
+        while self.next_pulse <= offset {
+            self.high_until = self.high_until.max(self.next_pulse + self.pulse);
+            self.next_pulse = self.next_pulse.saturating_add(self.interval());
+        }
+
+        offset < self.high_until
+    }
+
+    // exponentially distributed time to the next pulse
+    fn interval(&mut self) -> Duration {
+        let Some(mean) = self.mean_interval else {
+            return Duration::MAX;
+        };
+        let uniform = 1.0 - self.rng.next_f64();
+        Duration::try_from_secs_f64(-uniform.ln() * mean).unwrap_or(Duration::MAX)
+    }
+}
+
+// small generator of pseudo random numbers, good enough for noise and reproducible everywhere
+#[derive(Debug)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// FNV-1a, so each sensor gets different noise for the same seed
+fn name_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,75 @@
+#[cfg(feature = "tokio")]
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+// window of detections_last_minute
+pub const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+//
+// Snapshot of what a sensor did since it was created, see MotionSensor::stats()
+//
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensorStats {
+    // readings when sensor output was high
+    pub raw_highs: u64,
+    // high readings counted towards minimal_triggering_number
+    pub pre_detections: u64,
+    pub valid_detections: u64,
+    // high readings ignored during warm-up period or quarantine
+    pub suppressed: u64,
+    // high readings dropped because there were too many waiting for processing
+    pub dropped_readings: u64,
+    // high readings merged into one waiting for processing, see OverflowPolicy::Coalesce
+    pub coalesced_readings: u64,
+    // readings of sensor output skipped while the buffer was full, see OverflowPolicy::Block
+    pub blocked_readings: u64,
+    // valid detections which could not be sent to detection_channel (full or closed)
+    pub dropped_sends: u64,
+    // events which could not be sent to event channel (full or closed), see set_event_channel()
+    pub dropped_events: u64,
+    pub first_detection_time: Option<SystemTime>,
+    pub last_detection_time: Option<SystemTime>,
+    // last reading when sensor output was high
+    pub last_any_detection_time: Option<Instant>,
+    // valid detections within the last RATE_WINDOW
+    pub detections_last_minute: usize,
+}
+
+// counters kept by MotionSensor, the rest of SensorStats comes from the detection core
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default)]
+pub struct StatsCounters {
+    pub(crate) raw_highs: u64,
+    pub(crate) quarantined: u64,
+    pub(crate) blocked_readings: u64,
+    pub(crate) valid_detections: u64,
+    pub(crate) dropped_sends: u64,
+    pub(crate) dropped_events: u64,
+    pub(crate) first_detection_time: Option<SystemTime>,
+    // times of recent valid detections, older than RATE_WINDOW are removed
+    recent_detections: VecDeque<Instant>,
+}
+
+#[cfg(feature = "tokio")]
+impl StatsCounters {
+    pub(crate) fn detection(&mut self, now: Instant, time: SystemTime) {
+        self.valid_detections += 1;
+        self.first_detection_time.get_or_insert(time);
+
+        while self
+            .recent_detections
+            .front()
+            .is_some_and(|t| now.saturating_duration_since(*t) > RATE_WINDOW)
+        {
+            self.recent_detections.pop_front();
+        }
+        self.recent_detections.push_back(now);
+    }
+
+    pub(crate) fn detections_within_window(&self, now: Instant) -> usize {
+        self.recent_detections
+            .iter()
+            .filter(|t| now.saturating_duration_since(**t) <= RATE_WINDOW)
+            .count()
+    }
+}
@@ -0,0 +1,92 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tracing::warn;
+
+use super::bus::{EventBus, EventFilter, Subscription};
+use super::events::DetectionEvent;
+use super::motion::MotionSensor;
+
+//
+// Events from the bus as futures::Stream, so they can be used with StreamExt combinators and
+// select! instead of polling with try_recv(). Events dropped because the stream was not
+// polled fast enough are skipped, logged and counted (see lagged()).
+//
+// All streams of the bus share its capacity, so a single slow consumer loses events no matter
+// how fast others are. When a consumer of a single sensor must not be affected by the others,
+// use MotionSensor::event_stream() instead.
+//
+#[derive(Debug)]
+pub struct EventStream {
+    inner: BroadcastStream<DetectionEvent>,
+    filter: EventFilter,
+    lagged: u64,
+}
+
+impl EventStream {
+    // number of events dropped so far because the stream was not polled fast enough
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+}
+
+impl Stream for EventStream {
+    type Item = DetectionEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(event))) if self.filter.matches(&event) => {
+                    return Poll::Ready(Some(event));
+                }
+                Poll::Ready(Some(Ok(_))) => continue,
+                Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(n)))) => {
+                    self.lagged += n;
+                    warn!("event stream lagged behind, {n} event(s) dropped");
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Subscription {
+    pub fn into_stream(self) -> EventStream {
+        let (receiver, filter) = self.into_parts();
+        EventStream {
+            inner: BroadcastStream::new(receiver),
+            filter,
+            lagged: 0,
+        }
+    }
+}
+
+impl EventBus {
+    // events of all sensors publishing to the bus
+    pub fn stream(&self) -> EventStream {
+        self.subscribe().into_stream()
+    }
+
+    // events of a single sensor
+    pub fn sensor_stream(&self, sensor_name: &str) -> EventStream {
+        self.subscribe_filtered(EventFilter::default().sensor(sensor_name))
+            .into_stream()
+    }
+}
+
+impl MotionSensor {
+    //
+    // events of this sensor through its own mpsc channel, it replaces event channel of the
+    // sensor (see set_event_channel()). The stream doesn't depend on the bus and its other
+    // subscribers - events which don't fit into "capacity" are dropped by the sensor.
+    //
+    pub fn event_stream(&mut self, capacity: usize) -> ReceiverStream<DetectionEvent> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        self.set_event_channel(sender);
+        ReceiverStream::new(receiver)
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::backend::InputBackend;
+
+//
+// Trace file format (CSV), only changes of sensor output state (edges) are stored:
+//
+//   # pir-motion-sensor trace v1
+//   <microseconds since recording start>,<level: 1 - high, 0 - low>,<sensor name>
+//
+// Sensor name is the last column, so it may contain commas.
+//
+const TRACE_HEADER: &str = "# pir-motion-sensor trace v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub offset: Duration, // time since recording start
+    pub high: bool,
+}
+
+//
+// recorded edges of a single sensor
+//
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SensorTrace {
+    pub edges: Vec<Edge>,
+}
+
+impl SensorTrace {
+    pub fn new(edges: Vec<Edge>) -> Self {
+        Self { edges }
+    }
+
+    // state of sensor output at given moment, low before the first recorded edge
+    pub fn level_at(&self, offset: Duration) -> bool {
+        let idx = self.edges.partition_point(|e| e.offset <= offset);
+        idx > 0 && self.edges[idx - 1].high
+    }
+
+    // time of the last recorded edge
+    pub fn duration(&self) -> Duration {
+        self.edges.last().map(|e| e.offset).unwrap_or_default()
+    }
+}
+
+//
+// recorded edges of all sensors from a trace file
+//
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub sensors: HashMap<String, SensorTrace>,
+}
+
+impl Trace {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut trace = Trace::default();
+
+        for (line_number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            let invalid_line = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid trace line {}: {line}", line_number + 1),
+                )
+            };
+
+            let mut columns = line.splitn(3, ',');
+            let offset_micros: u64 = columns
+                .next()
+                .and_then(|c| c.parse().ok())
+                .ok_or_else(invalid_line)?;
+            let high = match columns.next() {
+                Some("1") => true,
+                Some("0") => false,
+                _ => return Err(invalid_line()),
+            };
+            let sensor_name = columns.next().ok_or_else(invalid_line)?;
+
+            trace
+                .sensors
+                .entry(sensor_name.to_string())
+                .or_default()
+                .edges
+                .push(Edge {
+                    offset: Duration::from_micros(offset_micros),
+                    high,
+                });
+        }
+
+        // recorder writes edges in order, but hand-written files may not
+        for sensor_trace in trace.sensors.values_mut() {
+            sensor_trace.edges.sort_by_key(|e| e.offset);
+        }
+
+        Ok(trace)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut edges: Vec<(&String, &Edge)> = self
+            .sensors
+            .iter()
+            .flat_map(|(name, t)| t.edges.iter().map(move |e| (name, e)))
+            .collect();
+        edges.sort_by_key(|(name, e)| (e.offset, *name));
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{TRACE_HEADER}")?;
+        for (name, edge) in edges {
+            write_edge(&mut file, name, edge)?;
+        }
+        file.flush()
+    }
+
+    pub fn sensor(&self, sensor_name: &str) -> Option<&SensorTrace> {
+        self.sensors.get(sensor_name)
+    }
+}
+
+fn write_edge(out: &mut impl Write, sensor_name: &str, edge: &Edge) -> io::Result<()> {
+    writeln!(
+        out,
+        "{},{},{}",
+        edge.offset.as_micros(),
+        u8::from(edge.high),
+        sensor_name
+    )
+}
+
+//
+// Recorder shared by many sensors (it's cheap to clone) - see MotionSensor::record_trace().
+// Offsets of all sensors are counted from "started_at", which should come from the clock of
+// the sensors (MotionSensor::now()), so traces of sensors in virtual time are recorded too.
+//
+#[derive(Debug, Clone)]
+pub struct TraceRecorder {
+    started_at: Instant,
+    output: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl TraceRecorder {
+    pub fn create(path: impl AsRef<Path>, started_at: Instant) -> io::Result<Self> {
+        let mut output = BufWriter::new(File::create(path)?);
+        writeln!(output, "{TRACE_HEADER}")?;
+
+        Ok(Self {
+            started_at,
+            output: Arc::new(Mutex::new(output)),
+        })
+    }
+
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    pub fn record(&self, sensor_name: &str, at: Instant, high: bool) -> io::Result<()> {
+        let edge = Edge {
+            offset: at.saturating_duration_since(self.started_at),
+            high,
+        };
+
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        write_edge(&mut *output, sensor_name, &edge)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.output
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .flush()
+    }
+}
+
+//
+// recorder attached to a single sensor - only changes of output state are written
+//
+#[derive(Debug)]
+pub struct SensorRecorder {
+    recorder: TraceRecorder,
+    last_level: Option<bool>,
+}
+
+impl SensorRecorder {
+    pub fn new(recorder: TraceRecorder) -> Self {
+        Self {
+            recorder,
+            last_level: None,
+        }
+    }
+
+    pub fn sample(&mut self, sensor_name: &str, now: Instant, high: bool) -> io::Result<()> {
+        if self.last_level == Some(high) {
+            return Ok(());
+        }
+        self.last_level = Some(high);
+        self.recorder.record(sensor_name, now, high)
+    }
+}
+
+impl Drop for SensorRecorder {
+    fn drop(&mut self) {
+        let _ = self.recorder.flush();
+    }
+}
+
+//
+// backend which plays back recorded trace, time is counted from the first reading
+//
+#[derive(Debug)]
+pub struct ReplayInput {
+    trace: SensorTrace,
+    started_at: Option<Instant>,
+}
+
+impl ReplayInput {
+    pub fn new(trace: SensorTrace) -> Self {
+        Self {
+            trace,
+            started_at: None,
+        }
+    }
+
+    // true when every recorded edge was already played back
+    pub fn is_finished(&self, now: Instant) -> bool {
+        self.started_at
+            .is_some_and(|s| now.saturating_duration_since(s) > self.trace.duration())
+    }
+}
+
+impl InputBackend for ReplayInput {
+    fn is_high(&mut self, now: Instant) -> bool {
+        let started_at = *self.started_at.get_or_insert(now);
+        self.trace
+            .level_at(now.saturating_duration_since(started_at))
+    }
+}
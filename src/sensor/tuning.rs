@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::clock::{Clock, ManualClock};
+use super::config::SensorConfig;
+use super::motion::MotionSensor;
+use super::trace::{ReplayInput, SensorTrace};
+
+// virtual time step of the replay, it's similar to what reading loop in helpers does
+const REPLAY_STEP: Duration = Duration::from_millis(1);
+
+//
+// Offline evaluation of sensor settings: recorded trace is replayed (in virtual time) through
+// MotionSensor with given configuration and valid detections are compared with intervals of
+// "real motion" labelled by hand.
+//
+
+// interval when there was a real motion in front of the sensor (offsets from trace start)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MotionInterval {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+//
+// labels file format (CSV) - one interval per line, miliseconds since trace start:
+//
+//   # start,end
+//   1200,4500
+//
+pub fn load_labels(path: impl AsRef<Path>) -> io::Result<Vec<MotionInterval>> {
+    let file = File::open(path)?;
+    let mut labels = Vec::new();
+
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let interval = line.split_once(',').and_then(|(start, end)| {
+            let start: u64 = start.trim().parse().ok()?;
+            let end: u64 = end.trim().parse().ok()?;
+            (start <= end).then(|| MotionInterval {
+                start: Duration::from_millis(start),
+                end: Duration::from_millis(end),
+            })
+        });
+
+        match interval {
+            Some(interval) => labels.push(interval),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid labels line {}: {line}", line_number + 1),
+                ));
+            }
+        }
+    }
+
+    Ok(labels)
+}
+
+// values checked for each parameter, every combination is evaluated
+#[derive(Debug, Clone)]
+pub struct TuningRange {
+    pub refresh_rate_milisecs: Vec<u64>,
+    pub motion_time_period_milisecs: Vec<u64>,
+    pub minimal_triggering_number: Vec<i16>,
+    // valid detection which happens up to this time after the end of labelled interval
+    // is still counted as detection of this motion
+    pub tolerance_milisecs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TuningResult {
+    pub config: SensorConfig,
+    pub valid_detections: usize,
+    // valid detections which match any labelled interval
+    pub true_positives: usize,
+    pub false_positives: usize,
+    // labelled intervals with at least one valid detection
+    pub detected_intervals: usize,
+    pub missed_intervals: usize,
+    pub precision: f64,
+    pub recall: f64,
+    // average time from the start of labelled interval to its first valid detection
+    pub mean_latency: Option<Duration>,
+}
+
+impl TuningResult {
+    pub fn f1_score(&self) -> f64 {
+        if self.precision + self.recall == 0.0 {
+            return 0.0;
+        }
+        2.0 * self.precision * self.recall / (self.precision + self.recall)
+    }
+}
+
+//
+// replays the trace with given configuration, returns offsets of valid detections
+//
+pub async fn replay(trace: &SensorTrace, config: &SensorConfig) -> Vec<Duration> {
+    let clock = ManualClock::new();
+    let start = clock.system_now();
+
+    let (detections_in, mut detections_out) = mpsc::channel(100);
+    let mut sensor = MotionSensor::with_input(
+        config.clone(),
+        detections_in,
+        Box::new(ReplayInput::new(trace.clone())),
+    );
+    sensor.set_clock(Arc::new(clock.clone()));
+
+    // after the last edge sensor still needs some time to finish counting
+    let end = trace.duration()
+        + Duration::from_millis(config.motion_time_period_milisecs + config.refresh_rate_milisecs);
+
+    let mut detections = Vec::new();
+    let (mut trigger_count, mut last_check_time) = (0, sensor.now());
+
+    while clock.elapsed() <= end {
+        sensor.reading_from_sensor().await;
+        (trigger_count, last_check_time) = sensor
+            .process_detections(trigger_count, last_check_time)
+            .await;
+
+        while let Ok((_, time)) = detections_out.try_recv() {
+            detections.push(time.duration_since(start).unwrap_or_default());
+        }
+
+        clock.advance(REPLAY_STEP);
+    }
+
+    detections
+}
+
+pub async fn evaluate(
+    trace: &SensorTrace,
+    labels: &[MotionInterval],
+    config: &SensorConfig,
+    tolerance_milisecs: u64,
+) -> TuningResult {
+    let detections = replay(trace, config).await;
+    let tolerance = Duration::from_millis(tolerance_milisecs);
+
+    let matches = |interval: &MotionInterval, detection: &Duration| {
+        *detection >= interval.start && *detection <= interval.end + tolerance
+    };
+
+    let true_positives = detections
+        .iter()
+        .filter(|d| labels.iter().any(|i| matches(i, d)))
+        .count();
+
+    let latencies: Vec<Duration> = labels
+        .iter()
+        .filter_map(|i| {
+            detections
+                .iter()
+                .find(|d| matches(i, d))
+                .map(|d| *d - i.start)
+        })
+        .collect();
+
+    let precision = if detections.is_empty() {
+        0.0
+    } else {
+        true_positives as f64 / detections.len() as f64
+    };
+    let recall = if labels.is_empty() {
+        0.0
+    } else {
+        latencies.len() as f64 / labels.len() as f64
+    };
+
+    TuningResult {
+        config: config.clone(),
+        valid_detections: detections.len(),
+        true_positives,
+        false_positives: detections.len() - true_positives,
+        detected_intervals: latencies.len(),
+        missed_intervals: labels.len() - latencies.len(),
+        precision,
+        recall,
+        mean_latency: (!latencies.is_empty())
+            .then(|| latencies.iter().sum::<Duration>() / latencies.len() as u32),
+    }
+}
+
+//
+// evaluates every combination from the range, results are sorted from the best one:
+// higher F1 score first, then lower detection latency
+//
+pub async fn sweep(
+    trace: &SensorTrace,
+    labels: &[MotionInterval],
+    base_config: &SensorConfig,
+    range: &TuningRange,
+) -> Vec<TuningResult> {
+    let mut results = Vec::new();
+
+    for refresh_rate in range.refresh_rate_milisecs.iter() {
+        for motion_time_period in range.motion_time_period_milisecs.iter() {
+            for minimal_triggering_number in range.minimal_triggering_number.iter() {
+                let config = SensorConfig {
+                    refresh_rate_milisecs: *refresh_rate,
+                    motion_time_period_milisecs: *motion_time_period,
+                    minimal_triggering_number: *minimal_triggering_number,
+                    ..base_config.clone()
+                };
+
+                results.push(evaluate(trace, labels, &config, range.tolerance_milisecs).await);
+            }
+        }
+    }
+
+    results.sort_by(|a, b| {
+        b.f1_score().total_cmp(&a.f1_score()).then(
+            a.mean_latency
+                .unwrap_or(Duration::MAX)
+                .cmp(&b.mean_latency.unwrap_or(Duration::MAX)),
+        )
+    });
+
+    results
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+
+use crate::sensor::events::{DetectionEvent, EventKind};
+
+//
+// Every event of sensors (valid detections, faults, lifecycle, etc.) is appended to a file as
+// a single JSON line, see event_json() for the format. When the file grows over "max_bytes" it's
+// rotated like logrotate does: events.jsonl -> events.jsonl.1 -> events.jsonl.2 ...
+//
+#[derive(Deserialize, Debug, Clone)]
+pub struct JsonLinesConfig {
+    pub path: PathBuf,
+    // 0 - file is never rotated
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    // number of rotated files which are kept, the oldest one is removed
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+fn default_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    config: JsonLinesConfig,
+    file: File,
+    // current size of the file
+    written: u64,
+}
+
+impl JsonLinesSink {
+    // events are appended to already existing file
+    pub fn new(config: JsonLinesConfig) -> io::Result<Self> {
+        let file = Self::open(&config)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            config,
+            file,
+            written,
+        })
+    }
+
+    pub fn write(&mut self, event: &DetectionEvent) -> io::Result<()> {
+        let line = format!("{}\n", event_json(event));
+
+        if self.config.max_bytes > 0
+            && self.written > 0
+            && self.written + line.len() as u64 > self.config.max_bytes
+        {
+            self.rotate()?;
+        }
+
+        // the whole line is written at once, so the file is never left with half of it
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+
+        Ok(())
+    }
+
+    pub async fn run(mut self, mut events: Receiver<DetectionEvent>, stop: Arc<CancellationToken>) {
+        loop {
+            tokio::select! {
+                _ = stop.cancelled() => break,
+                event = events.recv() => match event {
+                    Some(event) => self.write_or_warn(&event),
+                    None => break,
+                },
+            }
+        }
+
+        // events which were already sent before stop are not lost
+        while let Ok(event) = events.try_recv() {
+            self.write_or_warn(&event);
+        }
+    }
+
+    fn write_or_warn(&mut self, event: &DetectionEvent) {
+        if let Err(e) = self.write(event) {
+            warn!(
+                "jsonl: cannot write event to {}: {e}",
+                self.config.path.display()
+            );
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| {
+            let mut path = self.config.path.clone().into_os_string();
+            path.push(format!(".{n}"));
+            PathBuf::from(path)
+        };
+
+        if self.config.max_files == 0 {
+            fs::remove_file(&self.config.path)?;
+        } else {
+            for n in (1..self.config.max_files).rev() {
+                match fs::rename(rotated(n), rotated(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.config.path, rotated(1))?;
+        }
+
+        self.file = Self::open(&self.config)?;
+        self.written = 0;
+
+        Ok(())
+    }
+
+    fn open(config: &JsonLinesConfig) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+    }
+}
+
+// single JSON line describing the event, it's used by "stdout_json" of pir-motiond as well
+pub fn event_json(event: &DetectionEvent) -> String {
+    let mut json = serde_json::json!({
+        "event": "detection",
+        "sensor": event.sensor_name,
+        "time": DateTime::<Utc>::from(event.time).to_rfc3339(),
+    });
+
+    match event.kind {
+        EventKind::Detection => {}
+        EventKind::Ready => json["event"] = "ready".into(),
+        EventKind::Fault(fault) => {
+            json["event"] = "fault".into();
+            json["fault"] = fault.as_str().into();
+        }
+        EventKind::FaultCleared(fault) => {
+            json["event"] = "fault_cleared".into();
+            json["fault"] = fault.as_str().into();
+        }
+        EventKind::SensorAdded => json["event"] = "sensor_added".into(),
+        EventKind::SensorRemoved => json["event"] = "sensor_removed".into(),
+        EventKind::SensitivityAdjusted { from, to } => {
+            json["event"] = "sensitivity_adjusted".into();
+            json["from"] = from.into();
+            json["to"] = to.into();
+        }
+    }
+
+    json.to_string()
+}
@@ -0,0 +1,10 @@
+//
+// sinks consume valid detections produced by sensors and deliver them somewhere else,
+// each sink is behind its own feature so it doesn't pull dependencies you don't need
+//
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "webhook")]
+pub mod webhook;
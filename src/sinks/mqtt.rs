@@ -0,0 +1,226 @@
+use log::{info, warn};
+use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, Packet, QoS};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::{Instant, sleep_until};
+use tokio_util::sync::CancellationToken;
+
+use crate::sensor::events::DetectionEvent;
+use crate::sinks::jsonl::event_json;
+
+// the broker is connected again after this time when connection is lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+//
+// Every event of sensors is published to MQTT broker as a single JSON message (the same as line
+// of jsonl sink, see event_json()) to "{topic_prefix}/{sensor name}" topic. Events published
+// while the broker is not available are kept in memory, up to "queue_capacity" of them.
+//
+#[derive(Deserialize, Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    // 0 - at most once, 1 - at least once, 2 - exactly once
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+    // broker keeps the last event of each sensor for new subscribers
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default = "default_keep_alive_secs")]
+    pub keep_alive_secs: u64, // seconds
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: default_port(),
+            client_id: default_client_id(),
+            topic_prefix: default_topic_prefix(),
+            qos: default_qos(),
+            retain: false,
+            username: None,
+            password: None,
+            keep_alive_secs: default_keep_alive_secs(),
+            queue_capacity: default_queue_capacity(),
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.is_empty() {
+            return Err(String::from("host cannot be empty"));
+        }
+        if self.client_id.is_empty() {
+            return Err(String::from("client_id cannot be empty"));
+        }
+        if self.topic_prefix.contains(['+', '#']) {
+            return Err(String::from(
+                "topic_prefix cannot contain wildcards (+ or #)",
+            ));
+        }
+        if rumqttc::qos(self.qos).is_err() {
+            return Err(format!("qos must be 0, 1 or 2, not {}", self.qos));
+        }
+        if self.keep_alive_secs > 0 && self.keep_alive_secs < 5 {
+            return Err(String::from("keep_alive_secs must be 0 or at least 5"));
+        }
+        if self.queue_capacity == 0 {
+            return Err(String::from("queue_capacity must be greater than 0"));
+        }
+
+        Ok(())
+    }
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    String::from("pir-motiond")
+}
+
+fn default_topic_prefix() -> String {
+    String::from("pir-motion")
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+fn default_keep_alive_secs() -> u64 {
+    30
+}
+
+fn default_queue_capacity() -> usize {
+    1000
+}
+
+#[derive(Debug)]
+pub struct MqttSink {
+    config: MqttConfig,
+    qos: QoS,
+}
+
+impl MqttSink {
+    // connection to the broker is made by run()
+    pub fn new(config: MqttConfig) -> Result<Self, String> {
+        config.validate()?;
+        let qos = rumqttc::qos(config.qos).map_err(|e| e.to_string())?;
+
+        Ok(Self { config, qos })
+    }
+
+    // sensor name is a single level of topic, so it can't contain separators or wildcards
+    pub fn topic_for(&self, sensor_name: &str) -> String {
+        let sensor: String = sensor_name
+            .chars()
+            .map(|c| match c {
+                '/' | '+' | '#' => '_',
+                c => c,
+            })
+            .collect();
+
+        format!("{}/{sensor}", self.config.topic_prefix)
+    }
+
+    //
+    // main loop of the sink: events are taken from the channel and published. When the channel
+    // is closed, the sink disconnects after the broker confirms every published event (with
+    // QoS 0 - after it's written to the connection).
+    //
+    pub async fn run(self, mut events: Receiver<DetectionEvent>, stop: Arc<CancellationToken>) {
+        let mut options = MqttOptions::new(
+            self.config.client_id.clone(),
+            self.config.host.clone(),
+            self.config.port,
+        );
+        options.set_keep_alive(Duration::from_secs(self.config.keep_alive_secs));
+        if let Some(username) = self.config.username.as_ref() {
+            let password = self.config.password.clone().unwrap_or_default();
+            options.set_credentials(username, password);
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, self.config.queue_capacity);
+        let mut events_open = true;
+        // published events which are not confirmed by the broker yet
+        let mut unconfirmed = 0usize;
+        let mut disconnecting = false;
+        // None - broker is connected (or being connected)
+        let mut reconnect_at: Option<Instant> = None;
+
+        loop {
+            if !events_open && unconfirmed == 0 && !disconnecting {
+                if client.try_disconnect().is_err() {
+                    break;
+                }
+                disconnecting = true;
+            }
+
+            tokio::select! {
+                _ = stop.cancelled() => break,
+                event = events.recv(), if events_open => match event {
+                    Some(event) => {
+                        if self.publish(&client, &event) {
+                            unconfirmed += 1;
+                        }
+                    }
+                    None => events_open = false,
+                },
+                _ = sleep_until(reconnect_at.unwrap_or_else(Instant::now)), if reconnect_at.is_some() => {
+                    reconnect_at = None;
+                }
+                notification = eventloop.poll(), if reconnect_at.is_none() => match notification {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("mqtt: connected to {}:{}", self.config.host, self.config.port);
+                    }
+                    Ok(Event::Outgoing(Outgoing::Publish(_))) if self.qos == QoS::AtMostOnce => {
+                        unconfirmed = unconfirmed.saturating_sub(1);
+                    }
+                    Ok(Event::Incoming(Packet::PubAck(_) | Packet::PubComp(_))) => {
+                        unconfirmed = unconfirmed.saturating_sub(1);
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("mqtt: connection to {}:{} failed: {e}", self.config.host, self.config.port);
+                        // the sink is stopping, events which were not confirmed yet are lost
+                        if !events_open {
+                            break;
+                        }
+                        reconnect_at = Some(Instant::now() + RECONNECT_DELAY);
+                    }
+                },
+            }
+        }
+    }
+
+    // false - event is dropped
+    fn publish(&self, client: &AsyncClient, event: &DetectionEvent) -> bool {
+        let topic = self.topic_for(&event.sensor_name);
+        let payload = event_json(event);
+
+        match client.try_publish(&topic, self.qos, self.config.retain, payload) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("mqtt: cannot publish event to {topic}: {e}");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::Receiver;
+use tokio::time::{Instant, sleep_until};
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_PAYLOAD_TEMPLATE: &str = r#"{"sensor":"{sensor}","time":"{timestamp}"}"#;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    // urls used for every sensor which has no entry in "routes"
+    #[serde(default)]
+    pub urls: Vec<String>,
+    // sensor name -> urls, replaces "urls" for this particular sensor
+    #[serde(default)]
+    pub routes: HashMap<String, Vec<String>>,
+    // body of POST request, supported placeholders:
+    //   {sensor}       - sensor name (JSON escaped, without quotes)
+    //   {timestamp}    - detection time in RFC 3339 format (UTC)
+    //   {timestamp_ms} - detection time as miliseconds since unix epoch
+    #[serde(default = "default_payload_template")]
+    pub payload_template: String,
+    #[serde(default = "default_timeout_milisecs")]
+    pub timeout_milisecs: u64, // miliseconds
+    #[serde(default)]
+    pub retry: RetryConfig,
+    // file where not yet delivered requests are kept, so they survive network outages
+    // and restarts. None - requests are kept only in memory
+    #[serde(default)]
+    pub queue_path: Option<PathBuf>,
+    // maximum number of not yet delivered requests, the oldest ones are dropped first
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub initial_backoff_milisecs: u64, // miliseconds
+    pub max_backoff_milisecs: u64,     // miliseconds
+    // 0 - request is retried until it's delivered (or pushed out of the full queue). Requests
+    // rejected with 4xx status (except 408 and 429) are never retried.
+    pub max_attempts: u32,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_milisecs: 500,
+            max_backoff_milisecs: 60_000,
+            max_attempts: 0,
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            routes: HashMap::new(),
+            payload_template: default_payload_template(),
+            timeout_milisecs: default_timeout_milisecs(),
+            retry: RetryConfig::default(),
+            queue_path: None,
+            queue_capacity: default_queue_capacity(),
+        }
+    }
+}
+
+fn default_payload_template() -> String {
+    String::from(DEFAULT_PAYLOAD_TEMPLATE)
+}
+
+fn default_timeout_milisecs() -> u64 {
+    5000
+}
+
+fn default_queue_capacity() -> usize {
+    1000
+}
+
+// single POST request waiting for delivery, this is also the format of queue file (one JSON per line)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueuedRequest {
+    pub url: String,
+    pub body: String,
+    pub attempts: u32,
+}
+
+#[derive(Debug)]
+pub struct WebhookSink {
+    config: WebhookConfig,
+    client: reqwest::Client,
+    queue: VecDeque<QueuedRequest>,
+    // url -> time of next attempt after failed request, urls without entry can be used immediately
+    retry_at: HashMap<String, Instant>,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> io::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_milisecs))
+            .build()
+            .map_err(io::Error::other)?;
+
+        let mut sink = Self {
+            config,
+            client,
+            queue: VecDeque::new(),
+            retry_at: HashMap::new(),
+        };
+
+        // requests which were not delivered before last shutdown
+        sink.load_queue()?;
+
+        if !sink.queue.is_empty() {
+            info!(
+                "webhook: {} not delivered request(s) restored from queue file",
+                sink.queue.len()
+            );
+        }
+
+        Ok(sink)
+    }
+
+    pub fn urls_for(&self, sensor_name: &str) -> &[String] {
+        self.config
+            .routes
+            .get(sensor_name)
+            .unwrap_or(&self.config.urls)
+    }
+
+    pub fn render_payload(&self, sensor_name: &str, detection_time: SystemTime) -> String {
+        // sensor name is put inside of JSON string, so it has to be escaped
+        let sensor = serde_json::to_string(sensor_name).unwrap_or_default();
+        let sensor = &sensor[1..sensor.len() - 1];
+
+        let timestamp = DateTime::<Utc>::from(detection_time).to_rfc3339();
+        let timestamp_ms = detection_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // sensor name is replaced as the last one - it may contain placeholder-like text
+        self.config
+            .payload_template
+            .replace("{timestamp_ms}", &timestamp_ms.to_string())
+            .replace("{timestamp}", &timestamp)
+            .replace("{sensor}", sensor)
+    }
+
+    // number of requests waiting for delivery
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn enqueue(&mut self, sensor_name: &str, detection_time: SystemTime) {
+        let body = self.render_payload(sensor_name, detection_time);
+
+        for url in self.urls_for(sensor_name).to_vec() {
+            self.queue.push_back(QueuedRequest {
+                url,
+                body: body.clone(),
+                attempts: 0,
+            });
+        }
+
+        while self.queue.len() > self.config.queue_capacity {
+            if let Some(dropped) = self.queue.pop_front() {
+                warn!(
+                    "webhook: queue is full, dropping request to {}",
+                    dropped.url
+                );
+            }
+        }
+
+        self.store_queue();
+    }
+
+    //
+    // main loop of the sink: valid detections are taken from the channel and delivered to
+    // configured urls. Failed requests are retried with exponential backoff, requests to other
+    // urls are delivered in the meantime. Order of detections is preserved for each url.
+    //
+    pub async fn run(
+        mut self,
+        mut detections: Receiver<(String, SystemTime)>,
+        stop: Arc<CancellationToken>,
+    ) {
+        let mut detections_open = true;
+
+        loop {
+            if !detections_open && self.queue.is_empty() {
+                break;
+            }
+
+            let delivery_time = self.next_delivery();
+
+            tokio::select! {
+                _ = stop.cancelled() => break,
+                detection = detections.recv(), if detections_open => match detection {
+                    Some((sensor_name, detection_time)) => self.enqueue(&sensor_name, detection_time),
+                    None => detections_open = false,
+                },
+                _ = sleep_until(delivery_time.unwrap_or_else(Instant::now)), if delivery_time.is_some() => {
+                    self.deliver_next().await
+                }
+            }
+        }
+    }
+
+    // time when the first request can be sent, None when there are no requests
+    fn next_delivery(&self) -> Option<Instant> {
+        let now = Instant::now();
+
+        self.queue
+            .iter()
+            .map(|request| self.retry_at.get(&request.url).copied().unwrap_or(now))
+            .min()
+    }
+
+    async fn deliver_next(&mut self) {
+        // the oldest request to url which is not waiting for retry
+        let now = Instant::now();
+        let Some(idx) = self.queue.iter().position(|request| {
+            self.retry_at
+                .get(&request.url)
+                .is_none_or(|retry_at| *retry_at <= now)
+        }) else {
+            return;
+        };
+        let request = &mut self.queue[idx];
+
+        let result = self
+            .client
+            .post(&request.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(request.body.clone())
+            .send()
+            .await;
+
+        match result {
+            Ok(response) if response.status().is_success() => {
+                self.retry_at.remove(&request.url);
+                self.queue.remove(idx);
+            }
+            // request itself is wrong (unknown path, rejected payload, etc.), retrying won't help
+            Ok(response) if is_permanent_failure(response.status()) => {
+                warn!(
+                    "webhook: {} responded with {}, request is dropped",
+                    request.url,
+                    response.status()
+                );
+                self.retry_at.remove(&request.url);
+                self.queue.remove(idx);
+            }
+            failure => {
+                request.attempts += 1;
+
+                match failure {
+                    Ok(response) => warn!(
+                        "webhook: {} responded with {} (attempt {})",
+                        request.url,
+                        response.status(),
+                        request.attempts
+                    ),
+                    Err(e) => warn!(
+                        "webhook: cannot deliver to {}: {e} (attempt {})",
+                        request.url, request.attempts
+                    ),
+                }
+
+                let max_attempts = self.config.retry.max_attempts;
+                if max_attempts > 0 && request.attempts >= max_attempts {
+                    warn!("webhook: giving up on request to {}", request.url);
+                    self.retry_at.remove(&request.url);
+                    self.queue.remove(idx);
+                } else {
+                    let backoff = backoff_delay(&self.config.retry, request.attempts);
+                    self.retry_at
+                        .insert(request.url.clone(), Instant::now() + backoff);
+                }
+            }
+        }
+
+        self.store_queue();
+    }
+
+    fn load_queue(&mut self) -> io::Result<()> {
+        let Some(path) = self.config.queue_path.as_ref() else {
+            return Ok(());
+        };
+
+        let file = match fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            match serde_json::from_str::<QueuedRequest>(&line) {
+                Ok(request) => self.queue.push_back(request),
+                Err(e) => warn!("webhook: skipping invalid entry in queue file: {e}"),
+            }
+        }
+
+        // capacity could be lowered since the file was written - keep the newest requests
+        while self.queue.len() > self.config.queue_capacity {
+            self.queue.pop_front();
+        }
+
+        Ok(())
+    }
+
+    fn store_queue(&self) {
+        let Some(path) = self.config.queue_path.as_ref() else {
+            return;
+        };
+
+        // writing to temporary file first, then rename - queue file is never left half written
+        let tmp_path = path.with_extension("tmp");
+        let result = fs::File::create(&tmp_path).and_then(|mut file| {
+            for request in self.queue.iter() {
+                let line = serde_json::to_string(request).map_err(io::Error::other)?;
+                writeln!(file, "{line}")?;
+            }
+            file.sync_all()?;
+            fs::rename(&tmp_path, path)
+        });
+
+        if let Err(e) = result {
+            warn!("webhook: cannot store queue in {}: {e}", path.display());
+        }
+    }
+}
+
+// 4xx except "408 Request Timeout" and "429 Too Many Requests"
+fn is_permanent_failure(status: StatusCode) -> bool {
+    status.is_client_error()
+        && status != StatusCode::REQUEST_TIMEOUT
+        && status != StatusCode::TOO_MANY_REQUESTS
+}
+
+// initial * 2^(attempts - 1), limited by max_backoff_milisecs
+fn backoff_delay(retry: &RetryConfig, attempts: u32) -> Duration {
+    let multiplier = 2u64.saturating_pow(attempts.saturating_sub(1));
+    let milisecs = retry
+        .initial_backoff_milisecs
+        .saturating_mul(multiplier)
+        .min(retry.max_backoff_milisecs);
+
+    Duration::from_millis(milisecs)
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "tokio")]
+
+use pir_motion_sensor::actions::ActionConfig;
+
+///////////////////////////////////////////////////////////////////////////////
+// Actions: outputs turned on by valid detections, driven by ManualClock
+///////////////////////////////////////////////////////////////////////////////
+fn action_config(retrigger_extends: bool, inverted: bool) -> ActionConfig {
+    ActionConfig {
+        name: String::from("GarageLight"),
+        sensors: vec![String::from("Garage"), String::from("MainDoorSlow")],
+        pin_number: 21,
+        on_duration_milisecs: 1000,
+        retrigger_extends,
+        inverted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use pir_motion_sensor::actions::{Action, Actions, Override};
+    use pir_motion_sensor::sensor::backend::VirtualOutput;
+    use pir_motion_sensor::sensor::clock::ManualClock;
+
+    use super::*;
+
+    fn actions(config: ActionConfig) -> (Actions, VirtualOutput, ManualClock) {
+        let output = VirtualOutput::new();
+        let clock = ManualClock::new();
+
+        let mut actions = Actions::new(vec![Action::new(config, Box::new(output.clone()))]);
+        actions.set_clock(Arc::new(clock.clone()));
+
+        (actions, output, clock)
+    }
+
+    #[test]
+    fn output_is_on_for_given_time() {
+        let (mut actions, output, clock) = actions(action_config(true, false));
+        assert!(!output.is_high());
+
+        // not bound to this action
+        actions.handle_detection("Kitchen");
+        assert!(!output.is_high());
+
+        actions.handle_detection("Garage");
+        assert!(output.is_high());
+
+        clock.advance(Duration::from_millis(999));
+        actions.update();
+        assert!(output.is_high());
+
+        clock.advance(Duration::from_millis(1));
+        actions.update();
+        assert!(!output.is_high());
+    }
+
+    #[test]
+    fn retrigger_extends_on_time() {
+        let (mut actions, output, clock) = actions(action_config(true, false));
+
+        actions.handle_detection("Garage");
+        clock.advance(Duration::from_millis(800));
+        actions.handle_detection("MainDoorSlow");
+
+        clock.advance(Duration::from_millis(800));
+        actions.update();
+        assert!(output.is_high());
+
+        clock.advance(Duration::from_millis(200));
+        actions.update();
+        assert!(!output.is_high());
+    }
+
+    #[test]
+    fn retrigger_without_extension() {
+        let (mut actions, output, clock) = actions(action_config(false, false));
+
+        actions.handle_detection("Garage");
+        clock.advance(Duration::from_millis(800));
+        actions.handle_detection("Garage");
+
+        clock.advance(Duration::from_millis(200));
+        actions.update();
+        assert!(!output.is_high());
+    }
+
+    #[test]
+    fn inverted_output() {
+        let (mut actions, output, _clock) = actions(action_config(true, true));
+        assert!(output.is_high());
+
+        actions.handle_detection("Garage");
+        assert!(!output.is_high());
+        assert!(actions.action("GarageLight").unwrap().is_on());
+    }
+
+    #[test]
+    fn manual_override() {
+        let (mut actions, output, clock) = actions(action_config(true, false));
+
+        assert!(actions.set_override("GarageLight", Override::ForceOn));
+        assert!(output.is_high());
+        clock.advance(Duration::from_secs(60));
+        actions.update();
+        assert!(output.is_high());
+
+        // detections don't turn the output on while it's forced off
+        actions.set_override("GarageLight", Override::ForceOff);
+        actions.handle_detection("Garage");
+        assert!(!output.is_high());
+
+        // back to detections: the last one is still within on-time
+        actions.set_override("GarageLight", Override::Auto);
+        assert!(output.is_high());
+
+        assert!(!actions.set_override("Unknown", Override::ForceOn));
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::config::{AdaptiveConfig, SensorConfig};
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Adaptive sensitivity: minimal_triggering_number follows the noise floor
+///////////////////////////////////////////////////////////////////////////////
+fn adaptive_config() -> AdaptiveConfig {
+    AdaptiveConfig {
+        min_triggering_number: 2,
+        max_triggering_number: 5,
+        window_milisecs: 1000,
+        raise_above: 3,
+        lower_at_most: 0,
+    }
+}
+
+const WINDOW: Duration = Duration::from_millis(1000);
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use pir_motion_sensor::sensor::adaptive::Adaptation;
+    use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind};
+    use pir_motion_sensor::sensor::manager::SensorManager;
+    use pir_motion_sensor::sensor::trace::{ReplayInput, SensorTrace};
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn follows_noise_floor() {
+        let start = Instant::now();
+        let mut adaptation = Adaptation::new(adaptive_config(), 3, start);
+
+        // noisy window
+        assert_eq!(adaptation.update(start + WINDOW / 2, 4, false), None);
+        assert_eq!(adaptation.update(start + WINDOW, 0, false), Some(4));
+
+        // there was a real motion - window is not used
+        assert_eq!(adaptation.update(start + WINDOW * 2, 10, true), None);
+
+        // some noise, but not enough to change anything
+        assert_eq!(adaptation.update(start + WINDOW * 3, 2, false), None);
+        assert_eq!(adaptation.current(), 4);
+
+        // quiet windows, down to the lower bound
+        assert_eq!(adaptation.update(start + WINDOW * 4, 0, false), Some(3));
+        assert_eq!(adaptation.update(start + WINDOW * 5, 0, false), Some(2));
+        assert_eq!(adaptation.update(start + WINDOW * 6, 0, false), None);
+        assert_eq!(adaptation.current(), 2);
+
+        // very noisy windows, up to the upper bound
+        for window in 7..=9 {
+            assert!(
+                adaptation
+                    .update(start + WINDOW * window, 100, false)
+                    .is_some()
+            );
+        }
+        assert_eq!(adaptation.update(start + WINDOW * 10, 100, false), None);
+        assert_eq!(adaptation.current(), 5);
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(adaptive_config().validate().is_ok());
+
+        let mut bounds = adaptive_config();
+        bounds.min_triggering_number = 6;
+        assert!(bounds.validate().is_err());
+
+        // sensors created without validation don't panic
+        let mut adaptation = Adaptation::new(bounds, 3, Instant::now());
+        assert_eq!(adaptation.current(), 5);
+        assert_eq!(adaptation.update(Instant::now() + WINDOW, 0, false), None);
+
+        let mut thresholds = adaptive_config();
+        thresholds.lower_at_most = 3;
+        assert!(thresholds.validate().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quiet_sensor_is_adjusted() {
+        let (events_in, mut events_out) = mpsc::channel::<DetectionEvent>(10);
+
+        let mut manager = SensorManager::new();
+        manager.set_event_channel(events_in);
+        manager
+            .add_sensor_with_input(
+                SensorConfig {
+                    minimal_triggering_number: 4,
+                    adaptive: Some(adaptive_config()),
+                    ..common::sensor_config("Garage")
+                },
+                // output is never high
+                Box::new(ReplayInput::new(SensorTrace::new(Vec::new()))),
+            )
+            .unwrap();
+
+        let mut adjustments = Vec::new();
+        while adjustments.len() < 2 {
+            let event = events_out.recv().await.unwrap();
+            if let EventKind::SensitivityAdjusted { from, to } = event.kind {
+                adjustments.push((from, to));
+            }
+        }
+        assert_eq!(adjustments, vec![(4, 3), (3, 2)]);
+
+        let garage = manager.sensor("Garage").unwrap();
+        assert_eq!(garage.lock().await.minimal_triggering_number(), 2);
+
+        manager.stop().await;
+    }
+}
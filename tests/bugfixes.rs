@@ -1,3 +1,5 @@
+#![cfg(feature = "tokio")]
+
 use pir_motion_sensor::sensor::motion::MotionSensor;
 use tokio_util::sync::CancellationToken;
 
@@ -14,16 +16,17 @@ struct TestCase {
 mod tests {
     use std::{
         sync::Arc,
-        time::{Duration, Instant, SystemTime},
+        time::{Duration, SystemTime},
     };
 
     use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
     use tokio::sync::mpsc::{self, Receiver, Sender};
     use tokio::sync::Mutex;
+    use tokio::time::Instant;
 
     use super::*;
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn bugfix_test_detections() {
         #[allow(clippy::type_complexity)]
         let (detections_channel_in, mut detections_channel_out): (
@@ -0,0 +1,78 @@
+// fixtures shared by integration tests - not every test uses all of them
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use pir_motion_sensor::sensor::config::SensorConfig;
+use pir_motion_sensor::sensor::events::EventKind;
+use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
+use pir_motion_sensor::sensor::motion::MotionSensor;
+use pir_motion_sensor::sensor::trace::{Edge, ReplayInput, SensorTrace};
+use tokio::sync::{Mutex, mpsc};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+// valid detection needs 3 triggers within 500 ms, sensor output is read every 100 ms
+pub fn sensor_config(name: &str) -> SensorConfig {
+    SensorConfig {
+        name: String::from(name),
+        pin_number: 0, // not used by replayed traces
+        refresh_rate_milisecs: 100,
+        motion_time_period_milisecs: 500,
+        minimal_triggering_number: 3,
+        ..Default::default()
+    }
+}
+
+// edges given as (milisecond, level)
+pub fn sensor_trace(edges: &[(u64, bool)]) -> SensorTrace {
+    SensorTrace::new(
+        edges
+            .iter()
+            .map(|(milisecs, high)| Edge {
+                offset: Duration::from_millis(*milisecs),
+                high: *high,
+            })
+            .collect(),
+    )
+}
+
+// sensor output is high for the first "milisecs"
+pub fn motion_trace(milisecs: u64) -> SensorTrace {
+    sensor_trace(&[(0, true), (milisecs, false)])
+}
+
+//
+// runs detection threads of a single sensor for "milisecs", returns events of the sensor with
+// milisecond (since start) when they were received and the sensor itself
+//
+pub async fn replay(
+    config: SensorConfig,
+    trace: SensorTrace,
+    milisecs: u64,
+) -> (Vec<(u64, EventKind)>, Arc<Vec<Mutex<MotionSensor>>>) {
+    let (detections_in, _detections_out) = mpsc::channel(100);
+    let (events_in, mut events_out) = mpsc::channel(100);
+
+    let mut sensor =
+        MotionSensor::with_input(config, detections_in, Box::new(ReplayInput::new(trace)));
+    sensor.set_event_channel(events_in);
+
+    let sensors = Arc::new(vec![Mutex::new(sensor)]);
+    let token = Arc::new(CancellationToken::new());
+    spawn_detection_threads(sensors.clone(), token.clone());
+
+    let mut events = Vec::new();
+    let test_time_start = Instant::now();
+
+    while test_time_start.elapsed() < Duration::from_millis(milisecs) {
+        while let Ok(event) = events_out.try_recv() {
+            events.push((test_time_start.elapsed().as_millis() as u64, event.kind));
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    token.cancel();
+
+    (events, sensors)
+}
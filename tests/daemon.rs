@@ -0,0 +1,265 @@
+#![cfg(feature = "daemon")]
+
+///////////////////////////////////////////////////////////////////////////////
+// pir-motiond config file and systemd notifications
+///////////////////////////////////////////////////////////////////////////////
+const CONFIG: &str = r#"
+[[sensors]]
+name = "Garage"
+pin_number = 16
+refresh_rate_milisecs = 100
+motion_time_period_milisecs = 500
+minimal_triggering_number = 5
+
+[[sensors]]
+name = "MainDoorSlow"
+pin_number = 25
+refresh_rate_milisecs = 100
+motion_time_period_milisecs = 1000
+minimal_triggering_number = 4
+
+[sinks]
+stdout_json = true
+
+[sinks.webhook]
+urls = ["http://127.0.0.1:8080/motion"]
+queue_path = "/var/lib/pir-motiond/webhook-queue.jsonl"
+
+[sinks.jsonl]
+path = "/var/log/pir-motiond/events.jsonl"
+
+[sinks.mqtt]
+host = "192.168.1.10"
+topic_prefix = "home/motion"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixDatagram;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pir_motion_sensor::daemon::{DaemonConfig, detection_json, event_json, sd_notify_to};
+    use pir_motion_sensor::sensor::config::Bias;
+    use pir_motion_sensor::sensor::detector::OverflowPolicy;
+    use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind, FaultKind};
+
+    use super::*;
+
+    fn load(content: &str) -> std::io::Result<DaemonConfig> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pir-motiond.toml");
+        std::fs::write(&path, content).unwrap();
+        DaemonConfig::load(&path)
+    }
+
+    #[test]
+    fn config_file() {
+        let config = load(CONFIG).unwrap();
+
+        assert_eq!(config.sensors.len(), 2);
+        assert_eq!(config.sensors[1].name, "MainDoorSlow");
+        assert_eq!(config.sensors[1].minimal_triggering_number, 4);
+        assert!(config.sinks.stdout_json);
+
+        let webhook = config.sinks.webhook.unwrap();
+        assert_eq!(webhook.urls, vec!["http://127.0.0.1:8080/motion"]);
+        assert_eq!(webhook.queue_capacity, 1000); // default value
+
+        let jsonl = config.sinks.jsonl.unwrap();
+        assert_eq!(jsonl.max_files, 5); // default value
+
+        let mqtt = config.sinks.mqtt.unwrap();
+        assert_eq!(mqtt.topic_prefix, "home/motion");
+        assert_eq!((mqtt.port, mqtt.qos), (1883, 1)); // default values
+    }
+
+    #[test]
+    fn invalid_config_files() {
+        // the same pin used twice
+        let duplicated_pin = CONFIG.replace("pin_number = 25", "pin_number = 16");
+        assert!(load(&duplicated_pin).is_err());
+
+        let duplicated_name = CONFIG.replace("MainDoorSlow", "Garage");
+        assert!(load(&duplicated_name).is_err());
+
+        let action = "\n[[actions]]\nname = \"Light\"\nsensors = [\"Garage\"]\npin_number = 21\non_duration_milisecs = 1000\n";
+        assert!(load(&format!("{CONFIG}{action}")).is_ok());
+        // the same pin as sensor
+        assert!(load(&format!("{CONFIG}{}", action.replace("21", "16"))).is_err());
+        assert!(
+            load(&format!(
+                "{CONFIG}{}",
+                action.replace("[\"Garage\"]", "[\"Kitchen\"]")
+            ))
+            .is_err()
+        );
+
+        let adaptive = CONFIG.replace(
+            "minimal_triggering_number = 4",
+            "minimal_triggering_number = 4\nadaptive = { min_triggering_number = 3, max_triggering_number = 8 }",
+        );
+        let config = load(&adaptive).unwrap();
+        assert_eq!(config.sensors[1].adaptive.unwrap().window_milisecs, 600000);
+        assert!(
+            load(&adaptive.replace("max_triggering_number = 8", "max_triggering_number = 2"))
+                .is_err()
+        );
+
+        let buffered = CONFIG.replace(
+            "minimal_triggering_number = 4",
+            "minimal_triggering_number = 4\nbuffer_capacity = 20\noverflow_policy = \"coalesce\"",
+        );
+        let config = load(&buffered).unwrap();
+        assert_eq!(config.sensors[1].overflow_policy, OverflowPolicy::Coalesce);
+        assert_eq!(
+            config.sensors[0].overflow_policy,
+            OverflowPolicy::DropNewest
+        );
+        assert!(load(&buffered.replace("buffer_capacity = 20", "buffer_capacity = 0")).is_err());
+        assert!(load(&buffered.replace("\"coalesce\"", "\"random\"")).is_err());
+
+        let biased = CONFIG.replace(
+            "minimal_triggering_number = 4",
+            "minimal_triggering_number = 4\nbias = \"pull_down\"",
+        );
+        assert_eq!(load(&biased).unwrap().sensors[1].bias, Bias::PullDown);
+        // pulled to the active level
+        assert!(load(&biased.replace("pull_down", "pull_up")).is_err());
+
+        let simulation = "\n[simulation]\nseed = 7\n\n[[simulation.walks]]\nsensors = [\"Garage\", \"MainDoorSlow\"]\ndwell_milisecs = 2000\n\n[[simulation.noise]]\nrate_per_minute = 1.5\n";
+        let config = load(&format!("{CONFIG}{simulation}")).unwrap();
+        let scenario = config.simulation.unwrap();
+        assert_eq!(scenario.walks[0].sensors.len(), 2);
+        assert_eq!(scenario.noise[0].pulse_milisecs, 200); // default value
+        assert!(
+            load(&format!(
+                "{CONFIG}{}",
+                simulation.replace("Garage", "Kitchen")
+            ))
+            .is_err()
+        );
+        assert!(load(&format!("{CONFIG}{}", simulation.replace("1.5", "-1.5"))).is_err());
+        // simulated sensors and virtual outputs don't use GPIO pins
+        let action_on_sensor_pin = action.replace("21", "16");
+        let config = load(&format!(
+            "{duplicated_pin}{action_on_sensor_pin}{simulation}"
+        ))
+        .unwrap();
+        assert_eq!(config.actions[0].pin_number, 16);
+
+        let mqtt = CONFIG.replace("topic_prefix", "qos = 3\ntopic_prefix");
+        assert!(load(&mqtt).is_err());
+
+        assert!(load("[sinks]\nstdout_json = true\n").is_err());
+        assert!(load("[[sensors]]\nname = \"Garage\"\n").is_err());
+    }
+
+    #[test]
+    fn rules_in_config_file() {
+        let rules = r#"
+[[actions]]
+name = "GarageLight"
+sensors = ["Garage"]
+pin_number = 21
+on_duration_milisecs = 30000
+
+[[zones]]
+name = "Entrance"
+sensors = ["MainDoorSlow"]
+
+[[rules]]
+name = "NightIntruder"
+when = ["Garage", "Entrance"]
+within_milisecs = 10000
+schedule = { from = "22:00", to = "06:00" }
+then = [
+  { type = "webhook", url = "http://127.0.0.1:8080/alarm" },
+  { type = "pulse", pin_number = 20, duration_milisecs = 2000 },
+  { type = "action", name = "GarageLight" },
+]
+"#;
+        let config = load(&format!("{CONFIG}{rules}")).unwrap();
+        assert_eq!(config.rules[0].then.len(), 3);
+
+        let pulses = config.pulse_actions();
+        assert_eq!(pulses.len(), 1);
+        assert_eq!(pulses[0].pin_number, 20);
+        assert_eq!(pulses[0].on_duration_milisecs, 2000);
+
+        let webhook = config.rules_webhook().unwrap();
+        assert_eq!(
+            webhook.routes["NightIntruder"],
+            vec!["http://127.0.0.1:8080/alarm"]
+        );
+
+        // unknown zone, pulse on a sensor pin, unknown action
+        assert!(
+            load(&format!(
+                "{CONFIG}{}",
+                rules.replace("\"Garage\", \"Entrance\"", "\"Garage\", \"Kitchen\"")
+            ))
+            .is_err()
+        );
+        assert!(
+            load(&format!(
+                "{CONFIG}{}",
+                rules.replace("pin_number = 20", "pin_number = 16")
+            ))
+            .is_err()
+        );
+        assert!(
+            load(&format!(
+                "{CONFIG}{}",
+                rules.replace("name = \"GarageLight\" }", "name = \"Light\" }")
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn detection_as_json_line() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        assert_eq!(
+            detection_json("Garage", time),
+            r#"{"event":"detection","sensor":"Garage","time":"2023-11-14T22:13:20.500+00:00"}"#
+        );
+
+        let fault = DetectionEvent {
+            sensor_name: String::from("Garage"),
+            time,
+            kind: EventKind::Fault(FaultKind::StuckHigh),
+        };
+        assert_eq!(
+            event_json(&fault),
+            r#"{"event":"fault","fault":"stuck_high","sensor":"Garage","time":"2023-11-14T22:13:20.500+00:00"}"#
+        );
+
+        let added = DetectionEvent {
+            sensor_name: String::from("Garage"),
+            time,
+            kind: EventKind::SensorAdded,
+        };
+        assert_eq!(
+            event_json(&added),
+            r#"{"event":"sensor_added","sensor":"Garage","time":"2023-11-14T22:13:20.500+00:00"}"#
+        );
+    }
+
+    #[test]
+    fn systemd_notification() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&socket_path).unwrap();
+
+        // socket from NOTIFY_SOCKET is given directly, environment of the tests is not changed
+        sd_notify_to(socket_path.as_os_str(), "READY=1").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        // nobody listens
+        drop(socket);
+        assert!(sd_notify_to(socket_path.as_os_str(), "READY=1").is_err());
+    }
+}
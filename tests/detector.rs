@@ -0,0 +1,166 @@
+use pir_motion_sensor::sensor::detector::{CoreEvent, DetectionCore, DetectorConfig};
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Synchronous detection core - no runtime, time is given by the test
+///////////////////////////////////////////////////////////////////////////////
+fn detector_config(warmup_milisecs: u64) -> DetectorConfig {
+    DetectorConfig {
+        refresh_rate: Duration::from_millis(100),
+        motion_time_period: Duration::from_millis(500),
+        minimal_triggering_number: 3,
+        warmup: Duration::from_millis(warmup_milisecs),
+        ..Default::default()
+    }
+}
+
+// sensor output read once per refresh, returns milisecond of every event
+fn run(
+    core: &mut DetectionCore,
+    high: impl Fn(u64) -> bool,
+    until_milisecs: u64,
+) -> Vec<(u64, CoreEvent)> {
+    (0..=until_milisecs)
+        .step_by(110)
+        .filter_map(|ms| {
+            core.update(Duration::from_millis(ms), high(ms))
+                .map(|event| (ms, event))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::detector::{OverflowPolicy, PENDING_CAPACITY};
+
+    use super::*;
+
+    #[test]
+    fn continuous_motion() {
+        let mut core = DetectionCore::new(detector_config(0));
+
+        let events = run(&mut core, |ms| ms < 1000, 2000);
+
+        // every third refresh gives a valid detection while output is high
+        assert_eq!(
+            events,
+            vec![
+                (330, CoreEvent::Detection),
+                (660, CoreEvent::Detection),
+                (990, CoreEvent::Detection)
+            ]
+        );
+        assert!(!core.is_warming_up(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn triggers_outside_motion_time_period() {
+        let mut core = DetectionCore::new(detector_config(0));
+
+        // single triggers a few seconds apart are never a valid detection
+        let events = run(&mut core, |ms| ms % 2200 == 0, 10_000);
+
+        assert_eq!(events, vec![]);
+        assert_eq!(core.pre_detections(), 5);
+        assert_eq!(core.trigger_count(), 1);
+    }
+
+    #[test]
+    fn warmup() {
+        let mut core = DetectionCore::new(detector_config(1000));
+
+        let events = run(&mut core, |_| true, 1500);
+
+        // the first reading after warm-up starts counting towards a valid detection
+        assert_eq!(
+            events,
+            vec![(1100, CoreEvent::Ready), (1430, CoreEvent::Detection)]
+        );
+        assert_eq!(core.warmup_triggers(), 10);
+        assert!(core.is_ready());
+    }
+
+    #[test]
+    fn motion_during_warmup_only() {
+        let mut core = DetectionCore::new(detector_config(1000));
+
+        // output read every milisecond, so the buffer is full of readings when warm-up ends
+        let events: Vec<(u64, CoreEvent)> = (0..=2000)
+            .filter_map(|ms| {
+                core.update(Duration::from_millis(ms), ms < 900)
+                    .map(|event| (ms, event))
+            })
+            .collect();
+
+        // they were taken during warm-up, so they are never validated
+        assert_eq!(events, vec![(1000, CoreEvent::Ready)]);
+        assert_eq!(core.warmup_triggers(), 18);
+        assert_eq!(core.pre_detections(), 0);
+        assert_eq!(core.pending(), 0);
+    }
+
+    #[test]
+    fn pending_readings_are_limited() {
+        let mut core = DetectionCore::new(detector_config(0));
+
+        for _ in 0..PENDING_CAPACITY {
+            assert!(core.sample(Duration::ZERO, true));
+        }
+        assert!(!core.sample(Duration::ZERO, true));
+        assert!(core.sample(Duration::ZERO, false));
+        assert_eq!(core.pending(), PENDING_CAPACITY);
+
+        // one pending reading per refresh
+        assert_eq!(core.poll(Duration::from_millis(50)), None);
+        assert_eq!(core.poll(Duration::from_millis(101)), None);
+        assert_eq!(core.pending(), PENDING_CAPACITY - 1);
+        assert_eq!(core.trigger_count(), 1);
+
+        core.clear_pending();
+        assert_eq!(core.poll(Duration::from_millis(202)), None);
+        assert_eq!(core.trigger_count(), 1);
+    }
+
+    // buffer of 3 readings, sensor output read every 10 ms during 100 ms of motion
+    fn overflow(policy: OverflowPolicy) -> DetectionCore {
+        let mut core = DetectionCore::new(DetectorConfig {
+            buffer_capacity: 3,
+            overflow_policy: policy,
+            ..detector_config(0)
+        });
+
+        for ms in (0..100).step_by(10) {
+            core.sample(Duration::from_millis(ms), true);
+        }
+        core
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let core = overflow(OverflowPolicy::DropNewest);
+        assert_eq!(core.pending(), 3);
+        assert_eq!(core.dropped_readings(), 7);
+        assert_eq!(core.oldest_pending(), Some(Duration::ZERO));
+
+        // the most recent readings are kept
+        let core = overflow(OverflowPolicy::DropOldest);
+        assert_eq!(core.pending(), 3);
+        assert_eq!(core.dropped_readings(), 7);
+        assert_eq!(core.oldest_pending(), Some(Duration::from_millis(70)));
+
+        // a single reading per refresh rate
+        let core = overflow(OverflowPolicy::Coalesce);
+        assert_eq!(core.pending(), 1);
+        assert_eq!(core.coalesced_readings(), 9);
+        assert_eq!(core.dropped_readings(), 0);
+
+        // refused, but not dropped
+        let mut core = overflow(OverflowPolicy::Block);
+        assert!(core.is_full());
+        assert_eq!(core.dropped_readings(), 0);
+        assert!(!core.sample(Duration::from_millis(100), true));
+        assert_eq!(core.poll(Duration::from_millis(101)), None);
+        assert!(!core.is_full());
+        assert!(core.sample(Duration::from_millis(110), true));
+    }
+}
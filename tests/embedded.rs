@@ -0,0 +1,105 @@
+use pir_motion_sensor::sensor::detector::DetectorConfig;
+use pir_motion_sensor::sensor::embedded::MonotonicClock;
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Detection core on embedded-hal pins (mocked) with a microcontroller clock
+///////////////////////////////////////////////////////////////////////////////
+fn detector_config() -> DetectorConfig {
+    DetectorConfig {
+        refresh_rate: Duration::from_millis(100),
+        motion_time_period: Duration::from_millis(500),
+        minimal_triggering_number: 3,
+        warmup: Duration::from_millis(300),
+        ..Default::default()
+    }
+}
+
+// uptime of a board which moves by "step" every time it's read
+struct StepClock {
+    now: Duration,
+    step: Duration,
+}
+
+impl MonotonicClock for StepClock {
+    fn now(&mut self) -> Duration {
+        let now = self.now;
+        self.now += self.step;
+        now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::MockError;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+    use pir_motion_sensor::sensor::detector::{CoreEvent, DetectionCore};
+    use pir_motion_sensor::sensor::embedded::EmbeddedDetector;
+
+    use super::*;
+
+    #[test]
+    fn same_detections_as_core() {
+        // output is high for 12 readings, then low
+        let levels: Vec<bool> = (0..20).map(|i| i < 12).collect();
+        let transactions: Vec<Transaction> = levels
+            .iter()
+            .map(|high| Transaction::get(if *high { State::High } else { State::Low }))
+            .collect();
+
+        // board is up for some time already, readings every 110 ms
+        let clock = StepClock {
+            now: Duration::from_secs(3600),
+            step: Duration::from_millis(110),
+        };
+        let mut detector =
+            EmbeddedDetector::new(PinMock::new(&transactions), clock, detector_config());
+
+        let mut core = DetectionCore::new(detector_config());
+        let mut expected = Vec::new();
+        let mut events = Vec::new();
+        for (i, high) in levels.iter().enumerate() {
+            let now = Duration::from_millis(110 * (i as u64 + 1));
+            expected.push(core.update(now, *high));
+            events.push(detector.poll().unwrap());
+        }
+
+        assert_eq!(events, expected);
+        let events: Vec<(usize, CoreEvent)> = events
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, event)| event.map(|event| (i, event)))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (2, CoreEvent::Ready),
+                (5, CoreEvent::Detection),
+                (8, CoreEvent::Detection),
+                (11, CoreEvent::Detection)
+            ]
+        );
+        assert_eq!(detector.core().warmup_triggers(), 2);
+
+        let (mut pin, _) = detector.release();
+        pin.done();
+    }
+
+    #[test]
+    fn pin_error() {
+        let transactions =
+            [Transaction::get(State::High).with_error(MockError::Io(std::io::ErrorKind::Other))];
+        let clock = StepClock {
+            now: Duration::ZERO,
+            step: Duration::from_millis(110),
+        };
+        let mut detector =
+            EmbeddedDetector::new(PinMock::new(&transactions), clock, detector_config());
+
+        assert!(detector.poll().is_err());
+        assert_eq!(detector.core().pending(), 0);
+
+        let (mut pin, _) = detector.release();
+        pin.done();
+    }
+}
@@ -0,0 +1,185 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind, FaultKind};
+use std::time::{Duration, SystemTime};
+
+///////////////////////////////////////////////////////////////////////////////
+// Event bus: many subscribers, filters, lag reporting and streams
+///////////////////////////////////////////////////////////////////////////////
+fn event(sensor_name: &str, kind: EventKind) -> DetectionEvent {
+    DetectionEvent {
+        sensor_name: String::from(sensor_name),
+        time: SystemTime::now(),
+        kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use pir_motion_sensor::sensor::bus::{BusError, EventBus, EventFilter};
+    use pir_motion_sensor::sensor::events::EventType;
+    use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
+    use pir_motion_sensor::sensor::motion::MotionSensor;
+    use pir_motion_sensor::sensor::trace::ReplayInput;
+    use tokio::sync::{Mutex, mpsc};
+    use tokio_stream::StreamExt;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn every_subscriber_gets_its_events() {
+        let bus = EventBus::new(16);
+
+        let mut everything = bus.subscribe();
+        let mut garage = bus.subscribe_filtered(EventFilter::default().sensor("Garage"));
+        let mut faults = bus.subscribe_filtered(
+            EventFilter::default()
+                .event_type(EventType::Fault)
+                .event_type(EventType::FaultCleared),
+        );
+        assert_eq!(bus.subscriber_count(), 3);
+
+        bus.publish(event("Kitchen", EventKind::Detection));
+        bus.publish(event("Garage", EventKind::Detection));
+        bus.publish(event("Kitchen", EventKind::Fault(FaultKind::StuckHigh)));
+
+        assert_eq!(everything.recv().await.unwrap().sensor_name, "Kitchen");
+        assert_eq!(everything.recv().await.unwrap().sensor_name, "Garage");
+        assert_eq!(
+            everything.recv().await.unwrap().kind,
+            EventKind::Fault(FaultKind::StuckHigh)
+        );
+
+        assert_eq!(garage.recv().await.unwrap().kind, EventKind::Detection);
+
+        let fault = faults.recv().await.unwrap();
+        assert_eq!(fault.sensor_name, "Kitchen");
+        assert_eq!(fault.kind, EventKind::Fault(FaultKind::StuckHigh));
+
+        drop(bus);
+        assert_eq!(garage.recv().await, Err(BusError::Closed));
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_is_told_about_dropped_events() {
+        let bus = EventBus::new(2);
+        let mut slow = bus.subscribe();
+
+        for _ in 0..5 {
+            bus.publish(event("Garage", EventKind::Detection));
+        }
+
+        assert_eq!(slow.recv().await, Err(BusError::Lagged(3)));
+        assert_eq!(slow.lagged(), 3);
+
+        // the newest events are still there
+        assert!(slow.recv().await.is_ok());
+        assert!(slow.recv().await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sensors_publish_to_the_bus() {
+        let bus = EventBus::new(16);
+        let mut detections = bus.subscribe().into_detections(16);
+        let mut garage = bus.subscribe_filtered(EventFilter::default().sensor("Garage"));
+
+        let (detections_in, _detections_out) = mpsc::channel(10);
+        let mut sensors = Vec::new();
+        for name in ["Garage", "Kitchen"] {
+            let mut sensor = MotionSensor::with_input(
+                common::sensor_config(name),
+                detections_in.clone(),
+                Box::new(ReplayInput::new(common::motion_trace(700))),
+            );
+            sensor.set_event_bus(bus.clone());
+            sensors.push(Mutex::new(sensor));
+        }
+
+        let token = Arc::new(CancellationToken::new());
+        spawn_detection_threads(Arc::new(sensors), token.clone());
+
+        let (first, _) = detections.recv().await.unwrap();
+        let (second, _) = detections.recv().await.unwrap();
+        let mut names = vec![first, second];
+        names.sort();
+        assert_eq!(names, vec!["Garage", "Kitchen"]);
+
+        assert_eq!(garage.recv().await.unwrap().sensor_name, "Garage");
+
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn events_as_streams() {
+        let bus = EventBus::new(16);
+
+        let merged = bus.stream();
+        let mut kitchen = bus.sensor_stream("Kitchen");
+
+        bus.publish(event("Garage", EventKind::Detection));
+        bus.publish(event("Kitchen", EventKind::Ready));
+        bus.publish(event("Kitchen", EventKind::Detection));
+        drop(bus);
+
+        let detections: Vec<String> = merged
+            .filter(|event| event.kind == EventKind::Detection)
+            .map(|event| event.sensor_name)
+            .collect()
+            .await;
+        assert_eq!(detections, vec!["Garage", "Kitchen"]);
+
+        assert_eq!(kitchen.next().await.unwrap().kind, EventKind::Ready);
+        assert_eq!(kitchen.next().await.unwrap().kind, EventKind::Detection);
+        assert!(kitchen.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn lagging_stream_skips_dropped_events() {
+        let bus = EventBus::new(2);
+        let mut stream = bus.stream();
+
+        for _ in 0..5 {
+            bus.publish(event("Garage", EventKind::Detection));
+        }
+        drop(bus);
+
+        assert!(stream.next().await.is_some());
+        assert!(stream.next().await.is_some());
+        assert!(stream.next().await.is_none());
+        assert_eq!(stream.lagged(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sensor_stream_without_the_bus() {
+        // nobody reads the bus, so it's lagging all the time
+        let bus = EventBus::new(1);
+        let mut lagging = bus.stream();
+
+        let (detections_in, _detections_out) = mpsc::channel(10);
+        let mut sensor = MotionSensor::with_input(
+            common::sensor_config("Garage"),
+            detections_in,
+            Box::new(ReplayInput::new(common::motion_trace(700))),
+        );
+        sensor.set_event_bus(bus.clone());
+        let garage = sensor.event_stream(16);
+
+        let token = Arc::new(CancellationToken::new());
+        let tasks = spawn_detection_threads(Arc::new(vec![Mutex::new(sensor)]), token);
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        tasks.shutdown().await;
+        drop(bus);
+
+        let kinds: Vec<EventKind> = garage.map(|event| event.kind).collect().await;
+        assert_eq!(kinds, vec![EventKind::Detection; 5]);
+
+        assert!(lagging.next().await.is_some());
+        assert!(lagging.next().await.is_none());
+        assert_eq!(lagging.lagged(), 4);
+    }
+}
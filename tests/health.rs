@@ -0,0 +1,88 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::config::{HealthConfig, SensorConfig};
+
+///////////////////////////////////////////////////////////////////////////////
+// Health supervision: stuck-high and dead sensors are reported as faults
+///////////////////////////////////////////////////////////////////////////////
+fn sensor_config(health: HealthConfig) -> SensorConfig {
+    SensorConfig {
+        health,
+        ..common::sensor_config("Garage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::events::{EventKind, FaultKind};
+    use pir_motion_sensor::sensor::trace::SensorTrace;
+
+    use super::*;
+
+    async fn replay(config: SensorConfig, trace: SensorTrace, time: u64) -> Vec<(u64, EventKind)> {
+        common::replay(config, trace, time).await.0
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stuck_high_sensor_is_quarantined() {
+        let config = sensor_config(HealthConfig {
+            stuck_high_milisecs: Some(1000),
+            no_activity_milisecs: None,
+            quarantine: true,
+        });
+        let trace = common::sensor_trace(&[(0, true), (3000, false), (4000, false)]);
+
+        let events = replay(config, trace, 4000).await;
+
+        // detections before the fault, nothing while sensor is quarantined
+        assert_eq!(
+            events,
+            vec![
+                (304, EventKind::Detection),
+                (607, EventKind::Detection),
+                (910, EventKind::Detection),
+                (1002, EventKind::Fault(FaultKind::StuckHigh)),
+                (3001, EventKind::FaultCleared(FaultKind::StuckHigh)),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dead_sensor_is_reported() {
+        let config = sensor_config(HealthConfig {
+            stuck_high_milisecs: None,
+            no_activity_milisecs: Some(1000),
+            quarantine: false,
+        });
+        let trace = common::sensor_trace(&[(0, false), (2000, true), (2500, false), (3000, false)]);
+
+        let events = replay(config, trace, 3000).await;
+
+        // sensor works normally when it's active again
+        assert_eq!(
+            events,
+            vec![
+                (1002, EventKind::Fault(FaultKind::NoActivity)),
+                (2001, EventKind::FaultCleared(FaultKind::NoActivity)),
+                (2223, EventKind::Detection),
+                (2526, EventKind::Detection),
+                (2829, EventKind::Detection),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn health_supervision_is_disabled_by_default() {
+        let trace = common::sensor_trace(&[(0, true), (3000, false)]);
+
+        let events = replay(sensor_config(HealthConfig::default()), trace, 3000).await;
+
+        let detections = [304, 607, 910, 1213, 1516, 1819, 2122, 2425, 2728];
+        assert_eq!(
+            events,
+            detections.map(|ms| (ms, EventKind::Detection)).to_vec()
+        );
+    }
+}
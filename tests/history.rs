@@ -0,0 +1,200 @@
+#![cfg(feature = "history")]
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 2024-01-01 00:00:00 UTC
+const BASE_MILISECS: u64 = 1_704_067_200_000;
+const MINUTE: u64 = 60 * 1000;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+fn at(milisecs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(BASE_MILISECS + milisecs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use pir_motion_sensor::history::{
+        Bucket, BucketCount, History, HistoryConfig, RetentionPolicy,
+    };
+    use pir_motion_sensor::sensor::bus::EventBus;
+    use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind, FaultKind};
+    use tokio::sync::mpsc;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    fn open_history(dir: &tempfile::TempDir) -> History {
+        let mut config = HistoryConfig::new(dir.path().join("history.sqlite"));
+        config.episode_gap_milisecs = MINUTE;
+        History::open(config).unwrap()
+    }
+
+    #[test]
+    fn detections_episodes_and_aggregation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = open_history(&dir);
+
+        // garage: two episodes (second starts more than episode gap after the first one)
+        for milisecs in [HOUR, HOUR + 10_000, HOUR + 50_000, 3 * HOUR] {
+            history.record_detection("Garage", at(milisecs)).unwrap();
+        }
+        // kitchen: next day
+        history
+            .record_detection("Kitchen", at(DAY + 5 * HOUR))
+            .unwrap();
+        history
+            .record_fault("Kitchen", "stuck_active", at(DAY + 6 * HOUR))
+            .unwrap();
+
+        let whole_time = at(0)..at(7 * DAY);
+
+        let last = history
+            .last_detection(Some("Garage"), whole_time.clone())
+            .unwrap();
+        assert_eq!(last.unwrap().time, at(3 * HOUR));
+        assert!(
+            history
+                .last_detection(Some("Garage"), at(DAY)..at(2 * DAY))
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            history.detections(None, whole_time.clone()).unwrap().len(),
+            5
+        );
+
+        let episodes = history
+            .episodes(Some("Garage"), whole_time.clone())
+            .unwrap();
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(episodes[0].start, at(HOUR));
+        assert_eq!(episodes[0].end, at(HOUR + 50_000));
+        assert_eq!(episodes[0].detections, 3);
+        assert_eq!(episodes[1].detections, 1);
+
+        assert_eq!(
+            history
+                .aggregate(Some("Garage"), whole_time.clone(), Bucket::Hour)
+                .unwrap(),
+            vec![
+                BucketCount {
+                    start: at(HOUR),
+                    count: 3
+                },
+                BucketCount {
+                    start: at(3 * HOUR),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(
+            history
+                .aggregate(None, whole_time.clone(), Bucket::Day)
+                .unwrap(),
+            vec![
+                BucketCount {
+                    start: at(0),
+                    count: 4
+                },
+                BucketCount {
+                    start: at(DAY),
+                    count: 1
+                },
+            ]
+        );
+
+        let faults = history.faults(Some("Kitchen"), whole_time).unwrap();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind, "stuck_active");
+    }
+
+    #[test]
+    fn retention_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = HistoryConfig::new(dir.path().join("history.sqlite"));
+        config.retention = RetentionPolicy {
+            max_age_days: Some(7),
+            max_rows: Some(3),
+        };
+        let mut history = History::open(config).unwrap();
+
+        // one detection per day for 10 days, each one is a separate episode
+        for day in 0..10 {
+            history.record_detection("Garage", at(day * DAY)).unwrap();
+        }
+
+        history.apply_retention(at(10 * DAY)).unwrap();
+
+        let left = history.detections(None, at(0)..at(11 * DAY)).unwrap();
+        let left: Vec<SystemTime> = left.into_iter().map(|d| d.time).collect();
+        assert_eq!(left, vec![at(7 * DAY), at(8 * DAY), at(9 * DAY)]);
+        assert_eq!(
+            history.episodes(None, at(0)..at(11 * DAY)).unwrap().len(),
+            3
+        );
+    }
+
+    fn event(milisecs: u64, kind: EventKind) -> DetectionEvent {
+        DetectionEvent {
+            sensor_name: String::from("Garage"),
+            time: at(milisecs),
+            kind,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_events_from_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = open_history(&dir);
+
+        let (events_in, events_out) = mpsc::channel(10);
+        let token = Arc::new(CancellationToken::new());
+        let task = tokio::spawn(history.run(events_out, token));
+
+        for event in [
+            event(0, EventKind::SensorAdded),
+            event(0, EventKind::Detection),
+            event(1000, EventKind::Detection),
+            event(2000, EventKind::Fault(FaultKind::StuckHigh)),
+        ] {
+            events_in.send(event).await.unwrap();
+        }
+        drop(events_in);
+        task.await.unwrap();
+
+        // database is reopened - records are persistent
+        let history = open_history(&dir);
+        let episodes = history.episodes(Some("Garage"), at(0)..at(DAY)).unwrap();
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].detections, 2);
+
+        let faults = history.faults(Some("Garage"), at(0)..at(DAY)).unwrap();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind, "stuck_high");
+        assert_eq!(faults[0].time, at(2000));
+    }
+
+    #[tokio::test]
+    async fn records_events_from_bus() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = open_history(&dir);
+
+        let bus = EventBus::new(16);
+        let events = bus.subscribe().into_events(16);
+        let task = tokio::spawn(history.run(events, Arc::new(CancellationToken::new())));
+
+        bus.publish(event(0, EventKind::Detection));
+        bus.publish(event(1000, EventKind::Fault(FaultKind::NoActivity)));
+        drop(bus);
+        task.await.unwrap();
+
+        let history = open_history(&dir);
+        assert_eq!(history.detections(None, at(0)..at(DAY)).unwrap().len(), 1);
+        let faults = history.faults(None, at(0)..at(DAY)).unwrap();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind, "no_activity");
+    }
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::backend::InputBackend;
+use pir_motion_sensor::sensor::config::{ActiveLevel, Bias, SensorConfig};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+///////////////////////////////////////////////////////////////////////////////
+// Pull-up/pull-down resistors and active level of sensor output
+///////////////////////////////////////////////////////////////////////////////
+fn sensor_config(bias: Bias, active_level: ActiveLevel) -> SensorConfig {
+    SensorConfig {
+        bias,
+        active_level,
+        ..common::sensor_config("Garage")
+    }
+}
+
+// line which is always at the same level, remembers bias set by the sensor
+#[derive(Debug)]
+struct FixedLine {
+    high: bool,
+    bias: Arc<Mutex<Option<Bias>>>,
+}
+
+impl InputBackend for FixedLine {
+    fn is_high(&mut self, _now: Instant) -> bool {
+        self.high
+    }
+
+    fn set_bias(&mut self, bias: Bias) {
+        *self.bias.lock().unwrap() = Some(bias);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::manager::{ManagerError, SensorManager};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn bias_must_pull_to_inactive_level() {
+        assert!(
+            sensor_config(Bias::None, ActiveLevel::High)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            sensor_config(Bias::PullDown, ActiveLevel::High)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            sensor_config(Bias::PullUp, ActiveLevel::Low)
+                .validate()
+                .is_ok()
+        );
+
+        assert!(
+            sensor_config(Bias::PullUp, ActiveLevel::High)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            sensor_config(Bias::PullDown, ActiveLevel::Low)
+                .validate()
+                .is_err()
+        );
+
+        let mut manager = SensorManager::new();
+        let bias = Arc::new(Mutex::new(None));
+        assert!(matches!(
+            manager.add_sensor_with_input(
+                sensor_config(Bias::PullUp, ActiveLevel::High),
+                Box::new(FixedLine {
+                    high: false,
+                    bias: bias.clone()
+                }),
+            ),
+            Err(ManagerError::InvalidConfig(_))
+        ));
+        assert_eq!(*bias.lock().unwrap(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn active_low_output() {
+        let (detections_in, mut detections_out) = mpsc::channel(10);
+        let bias = Arc::new(Mutex::new(None));
+
+        let mut manager = SensorManager::new();
+        manager.set_detection_channel(detections_in);
+        manager
+            .add_sensor_with_input(
+                sensor_config(Bias::PullUp, ActiveLevel::Low),
+                // output low all the time - continuous motion
+                Box::new(FixedLine {
+                    high: false,
+                    bias: bias.clone(),
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(*bias.lock().unwrap(), Some(Bias::PullUp));
+
+        // bias configured outside of the crate is left alone
+        let untouched = Arc::new(Mutex::new(None));
+        manager
+            .add_sensor_with_input(
+                SensorConfig {
+                    name: String::from("Kitchen"),
+                    pin_number: 1,
+                    ..sensor_config(Bias::None, ActiveLevel::High)
+                },
+                Box::new(FixedLine {
+                    high: false,
+                    bias: untouched.clone(),
+                }),
+            )
+            .unwrap();
+        assert_eq!(*untouched.lock().unwrap(), None);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(detections_out.recv().await.unwrap().0, "Garage");
+
+        manager.stop().await;
+    }
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "jsonl")]
+
+use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind, FaultKind};
+use std::time::{Duration, UNIX_EPOCH};
+
+///////////////////////////////////////////////////////////////////////////////
+// JSON lines sink: every event in a file, rotated when it's too big
+///////////////////////////////////////////////////////////////////////////////
+fn event(kind: EventKind) -> DetectionEvent {
+    DetectionEvent {
+        sensor_name: String::from("Garage"),
+        time: UNIX_EPOCH + Duration::from_millis(1_700_000_000_500),
+        kind,
+    }
+}
+
+// length of a single line of detection event (with new line)
+const LINE_LENGTH: u64 = 79;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use pir_motion_sensor::sinks::jsonl::{JsonLinesConfig, JsonLinesSink};
+    use tokio::sync::mpsc;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    #[test]
+    fn rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let rotated = |n: usize| dir.path().join(format!("events.jsonl.{n}"));
+
+        let mut sink = JsonLinesSink::new(JsonLinesConfig {
+            path: path.clone(),
+            // two lines per file
+            max_bytes: 2 * LINE_LENGTH,
+            max_files: 2,
+        })
+        .unwrap();
+
+        for _ in 0..2 {
+            sink.write(&event(EventKind::Detection)).unwrap();
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), 2 * LINE_LENGTH);
+        assert!(!rotated(1).exists());
+
+        sink.write(&event(EventKind::Fault(FaultKind::StuckHigh)))
+            .unwrap();
+        assert_eq!(fs::metadata(rotated(1)).unwrap().len(), 2 * LINE_LENGTH);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"event\":\"fault\",\"fault\":\"stuck_high\",\"sensor\":\"Garage\",\"time\":\"2023-11-14T22:13:20.500+00:00\"}\n"
+        );
+
+        // the oldest file is removed
+        for _ in 0..4 {
+            sink.write(&event(EventKind::Detection)).unwrap();
+        }
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+        assert!(
+            fs::read_to_string(rotated(2))
+                .unwrap()
+                .starts_with("{\"event\":\"fault\"")
+        );
+
+        // appending to the existing file after restart
+        drop(sink);
+        let mut sink = JsonLinesSink::new(JsonLinesConfig {
+            path: path.clone(),
+            max_bytes: 2 * LINE_LENGTH,
+            max_files: 2,
+        })
+        .unwrap();
+        sink.write(&event(EventKind::Detection)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn events_sent_before_stop_are_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+
+        let sink = JsonLinesSink::new(JsonLinesConfig {
+            path: path.clone(),
+            max_bytes: 0,
+            max_files: 0,
+        })
+        .unwrap();
+
+        let (events_in, events_out) = mpsc::channel(10);
+        for kind in [
+            EventKind::SensorAdded,
+            EventKind::Ready,
+            EventKind::Detection,
+        ] {
+            events_in.send(event(kind)).await.unwrap();
+        }
+
+        let stop = Arc::new(CancellationToken::new());
+        stop.cancel();
+        sink.run(events_out, stop).await;
+
+        let content = fs::read_to_string(&path).unwrap();
+        let events: Vec<_> = content
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["event"].clone())
+            .collect();
+        assert_eq!(events, vec!["sensor_added", "ready", "detection"]);
+    }
+}
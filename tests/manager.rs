@@ -0,0 +1,192 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::config::SensorConfig;
+use pir_motion_sensor::sensor::trace::SensorTrace;
+
+///////////////////////////////////////////////////////////////////////////////
+// Sensor manager: every sensor in its own task, added and removed at runtime
+///////////////////////////////////////////////////////////////////////////////
+fn sensor_config(name: &str, pin_number: u8, refresh_rate_milisecs: u64) -> SensorConfig {
+    SensorConfig {
+        pin_number,
+        refresh_rate_milisecs,
+        motion_time_period_milisecs: refresh_rate_milisecs * 5,
+        ..common::sensor_config(name)
+    }
+}
+
+// sensor output is high all the time
+fn motion_trace() -> SensorTrace {
+    common::sensor_trace(&[(0, true)])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    use pir_motion_sensor::sensor::bus::EventBus;
+    use pir_motion_sensor::sensor::events::EventKind;
+    use pir_motion_sensor::sensor::manager::{ManagerError, SensorManager};
+    use pir_motion_sensor::sensor::motion::MotionSensor;
+    use pir_motion_sensor::sensor::stream::EventStream;
+    use pir_motion_sensor::sensor::trace::ReplayInput;
+    use tokio::sync::mpsc;
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    fn sensor(
+        name: &str,
+        pin_number: u8,
+        refresh_rate_milisecs: u64,
+        bus: &EventBus,
+    ) -> MotionSensor {
+        let (detections_in, _detections_out) = mpsc::channel(10);
+        let mut sensor = MotionSensor::with_input(
+            sensor_config(name, pin_number, refresh_rate_milisecs),
+            detections_in,
+            Box::new(ReplayInput::new(motion_trace())),
+        );
+        sensor.set_event_bus(bus.clone());
+        sensor
+    }
+
+    async fn next_detection(stream: &mut EventStream) -> String {
+        loop {
+            let event = stream.next().await.unwrap();
+            if event.kind == EventKind::Detection {
+                return event.sensor_name;
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sensors_run_at_their_own_cadence() {
+        let bus = EventBus::new(64);
+        let mut events = bus.stream();
+
+        let mut manager = SensorManager::new();
+        manager.add(sensor("Slow", 1, 100, &bus)).unwrap();
+        manager.add(sensor("Fast", 2, 10, &bus)).unwrap();
+        assert_eq!(manager.names(), vec!["Slow", "Fast"]);
+
+        // 3 triggers of the fast sensor, before the slow one reads its output twice
+        let started = Instant::now();
+        assert_eq!(next_detection(&mut events).await, "Fast");
+        assert_eq!(started.elapsed(), Duration::from_millis(33));
+
+        let mut slow = bus.sensor_stream("Slow");
+        assert_eq!(next_detection(&mut slow).await, "Slow");
+
+        manager.stop().await;
+        assert!(manager.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn locked_sensor_does_not_block_others() {
+        let bus = EventBus::new(64);
+        let mut kitchen = bus.sensor_stream("Kitchen");
+
+        let mut manager = SensorManager::new();
+        manager.add(sensor("Garage", 1, 10, &bus)).unwrap();
+        manager.add(sensor("Kitchen", 2, 10, &bus)).unwrap();
+
+        let garage = manager.sensor("Garage").unwrap();
+        let locked = garage.lock().await;
+
+        assert_eq!(next_detection(&mut kitchen).await, "Kitchen");
+
+        drop(locked);
+        manager.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sensors_added_and_removed_at_runtime() {
+        let bus = EventBus::new(64);
+        let mut events = bus.stream();
+
+        let mut manager = SensorManager::new();
+        manager.add(sensor("Garage", 1, 10, &bus)).unwrap();
+        assert_eq!(next_detection(&mut events).await, "Garage");
+
+        assert_eq!(
+            manager.add(sensor("Garage", 1, 10, &bus)),
+            Err(ManagerError::DuplicateName(String::from("Garage")))
+        );
+
+        manager.add(sensor("Kitchen", 2, 10, &bus)).unwrap();
+        manager.remove_sensor("Garage").await.unwrap();
+        assert_eq!(manager.names(), vec!["Kitchen"]);
+        assert!(manager.sensor("Garage").is_none());
+        assert_eq!(
+            manager.remove_sensor("Garage").await,
+            Err(ManagerError::NotFound(String::from("Garage")))
+        );
+
+        // only the remaining sensor still detects
+        let mut garage = bus.sensor_stream("Garage");
+        let mut all = bus.stream();
+        for _ in 0..3 {
+            assert_eq!(next_detection(&mut all).await, "Kitchen");
+        }
+        manager.stop().await;
+        drop(bus);
+        assert!(garage.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sensors_registered_from_config() {
+        let bus = EventBus::new(64);
+        let mut events = bus.stream();
+        let (detections_in, mut detections_out) = mpsc::channel(10);
+
+        let mut manager = SensorManager::new();
+        manager.set_detection_channel(detections_in);
+        manager.set_event_bus(bus.clone());
+
+        manager
+            .add_sensor_with_input(
+                sensor_config("Garage", 17, 10),
+                Box::new(ReplayInput::new(motion_trace())),
+            )
+            .unwrap();
+
+        assert_eq!(
+            manager.add_sensor(sensor_config("Garage", 18, 10)),
+            Err(ManagerError::DuplicateName(String::from("Garage")))
+        );
+
+        let listed = manager.list_sensors();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "Garage");
+        assert_eq!(listed[0].pin_number, 17);
+
+        let added = events.next().await.unwrap();
+        assert_eq!(added.sensor_name, "Garage");
+        assert_eq!(added.kind, EventKind::SensorAdded);
+
+        let (name, _) = detections_out.recv().await.unwrap();
+        assert_eq!(name, "Garage");
+
+        // pin is used only by sensors which read GPIO, see add_sensor()
+        manager
+            .add_sensor_with_input(
+                sensor_config("Kitchen", 17, 10),
+                Box::new(ReplayInput::new(motion_trace())),
+            )
+            .unwrap();
+        assert_eq!(manager.names(), vec!["Garage", "Kitchen"]);
+        manager.remove_sensor("Kitchen").await.unwrap();
+
+        manager.remove_sensor("Garage").await.unwrap();
+        assert!(manager.list_sensors().is_empty());
+
+        drop(manager);
+        drop(bus);
+        let kinds: Vec<EventKind> = events.map(|event| event.kind).collect().await;
+        assert_eq!(kinds.last(), Some(&EventKind::SensorRemoved));
+    }
+}
@@ -0,0 +1,231 @@
+#![cfg(feature = "mqtt")]
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+///////////////////////////////////////////////////////////////////////////////
+// Tests for MQTT sink - events are published to a local broker stub which
+// accepts every client and records topic, payload and QoS of every message
+///////////////////////////////////////////////////////////////////////////////
+#[derive(Clone, Default)]
+struct BrokerStub {
+    messages: Arc<Mutex<Vec<(String, String, u8)>>>, // topic, payload, qos
+}
+
+impl BrokerStub {
+    async fn start(listener: TcpListener) -> Self {
+        let stub = BrokerStub::default();
+        let messages = stub.messages.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let messages = messages.clone();
+                tokio::spawn(async move { serve(&mut stream, messages).await });
+            }
+        });
+
+        stub
+    }
+
+    fn messages(&self) -> Vec<(String, String, u8)> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+// packets of MQTT 3.1.1 needed by a client which only publishes
+async fn serve(stream: &mut TcpStream, messages: Arc<Mutex<Vec<(String, String, u8)>>>) {
+    while let Some((header, body)) = read_packet(stream).await {
+        let response: Vec<u8> = match header >> 4 {
+            // CONNECT -> CONNACK
+            1 => vec![0x20, 0x02, 0x00, 0x00],
+            // PUBLISH -> PUBACK (QoS 1) or PUBREC (QoS 2)
+            3 => {
+                let qos = (header >> 1) & 0x03;
+                let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                let topic = String::from_utf8_lossy(&body[2..2 + topic_len]).to_string();
+                let payload_start = if qos > 0 {
+                    topic_len + 4
+                } else {
+                    topic_len + 2
+                };
+                let payload = String::from_utf8_lossy(&body[payload_start..]).to_string();
+                messages.lock().unwrap().push((topic, payload, qos));
+
+                let id = &body[topic_len + 2..topic_len + 4];
+                match qos {
+                    0 => Vec::new(),
+                    1 => vec![0x40, 0x02, id[0], id[1]],
+                    _ => vec![0x50, 0x02, id[0], id[1]],
+                }
+            }
+            // PUBREL -> PUBCOMP
+            6 => vec![0x70, 0x02, body[0], body[1]],
+            // PINGREQ -> PINGRESP
+            12 => vec![0xd0, 0x00],
+            // DISCONNECT
+            _ => return,
+        };
+
+        if stream.write_all(&response).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let header = stream.read_u8().await.ok()?;
+
+    // remaining length - 7 bits per byte, the highest bit means there is one more byte
+    let mut length = 0usize;
+    for shift in (0..28).step_by(7) {
+        let byte = stream.read_u8().await.ok()?;
+        length |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).await.ok()?;
+    Some((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind, FaultKind};
+    use pir_motion_sensor::sinks::jsonl::event_json;
+    use pir_motion_sensor::sinks::mqtt::{MqttConfig, MqttSink};
+    use tokio::sync::mpsc;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    async fn wait_for(condition: impl Fn() -> bool, timeout_milisecs: u64) {
+        let start = Instant::now();
+        while !condition() && (start.elapsed().as_millis() as u64) < timeout_milisecs {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    fn event(sensor_name: &str, kind: EventKind) -> DetectionEvent {
+        DetectionEvent {
+            sensor_name: String::from(sensor_name),
+            time: UNIX_EPOCH + Duration::from_millis(1_700_000_000_500),
+            kind,
+        }
+    }
+
+    #[tokio::test]
+    async fn events_are_published() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stub = BrokerStub::start(listener).await;
+
+        let sink = MqttSink::new(MqttConfig {
+            host: String::from("127.0.0.1"),
+            port,
+            topic_prefix: String::from("home/motion"),
+            qos: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            sink.topic_for("Garage/Door #1"),
+            "home/motion/Garage_Door _1"
+        );
+
+        let (events_in, events_out) = mpsc::channel(10);
+        let task = tokio::spawn(sink.run(events_out, Arc::new(CancellationToken::new())));
+
+        let detection = event("Garage", EventKind::Detection);
+        let fault = event("Hall", EventKind::Fault(FaultKind::StuckHigh));
+        events_in.send(detection.clone()).await.unwrap();
+        events_in.send(fault.clone()).await.unwrap();
+        drop(events_in);
+
+        // everything is sent to the broker before the sink is finished
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            stub.messages(),
+            vec![
+                (
+                    String::from("home/motion/Garage"),
+                    event_json(&detection),
+                    2
+                ),
+                (String::from("home/motion/Hall"), event_json(&fault), 2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn broker_is_not_available() {
+        // nothing listens on this port yet
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let sink = MqttSink::new(MqttConfig {
+            host: String::from("127.0.0.1"),
+            port: addr.port(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let (events_in, events_out) = mpsc::channel(10);
+        let token = Arc::new(CancellationToken::new());
+        let task = tokio::spawn(sink.run(events_out, token.clone()));
+
+        // kept until the broker is available
+        events_in
+            .send(event("Garage", EventKind::Detection))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stub = BrokerStub::start(TcpListener::bind(addr).await.unwrap()).await;
+        wait_for(|| stub.messages().len() == 1, 5000).await;
+        token.cancel();
+        task.await.unwrap();
+
+        assert_eq!(stub.messages()[0].0, "pir-motion/Garage");
+        assert_eq!(stub.messages()[0].2, 1);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = |config: MqttConfig| MqttSink::new(config).err();
+
+        assert_eq!(config(MqttConfig::default()), None);
+        assert!(
+            config(MqttConfig {
+                qos: 3,
+                ..Default::default()
+            })
+            .is_some()
+        );
+        assert!(
+            config(MqttConfig {
+                topic_prefix: String::from("home/+"),
+                ..Default::default()
+            })
+            .is_some()
+        );
+        assert!(
+            config(MqttConfig {
+                host: String::new(),
+                ..Default::default()
+            })
+            .is_some()
+        );
+    }
+}
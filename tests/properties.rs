@@ -0,0 +1,327 @@
+#![cfg(feature = "tokio")]
+
+use pir_motion_sensor::sensor::clock::ManualClock;
+use pir_motion_sensor::sensor::config::SensorConfig;
+use pir_motion_sensor::sensor::detector::{
+    CoreEvent, DetectionCore, DetectorConfig, MAX_PENDING_CAPACITY, OverflowPolicy,
+};
+use pir_motion_sensor::sensor::trace::{Edge, SensorTrace};
+use proptest::prelude::*;
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Properties of detection logic over random configs and sensor readings. Time
+// comes from ManualClock moved forward by random steps, so every failing case
+// found by proptest is replayed exactly (see properties.proptest-regressions).
+// There is also a fuzz target with the same invariants in fuzz/ directory.
+///////////////////////////////////////////////////////////////////////////////
+fn overflow_policy() -> impl Strategy<Value = OverflowPolicy> {
+    prop_oneof![
+        Just(OverflowPolicy::DropNewest),
+        Just(OverflowPolicy::DropOldest),
+        Just(OverflowPolicy::Coalesce),
+        Just(OverflowPolicy::Block),
+    ]
+}
+
+fn detector_config() -> impl Strategy<Value = DetectorConfig> {
+    (
+        0..=300u64,
+        0..=2000u64,
+        1..=10i16,
+        prop_oneof![Just(0u64), 0..=1000u64],
+        1..=MAX_PENDING_CAPACITY,
+        overflow_policy(),
+    )
+        .prop_map(
+            |(refresh, period, min, warmup, capacity, policy)| DetectorConfig {
+                refresh_rate: Duration::from_millis(refresh),
+                motion_time_period: Duration::from_millis(period),
+                minimal_triggering_number: min,
+                warmup: Duration::from_millis(warmup),
+                buffer_capacity: capacity,
+                overflow_policy: policy,
+            },
+        )
+}
+
+// sensor output read after a random time (usually a few miliseconds, sometimes much later)
+fn readings() -> impl Strategy<Value = Vec<(u64, bool)>> {
+    prop::collection::vec(
+        (prop_oneof![4 => 0..=20u64, 1 => 0..=2000u64], any::<bool>()),
+        0..400,
+    )
+}
+
+// what happened during a run of detection core
+#[derive(Debug, Default)]
+struct Run {
+    // time of reading of every trigger counted towards valid detections (after warm-up)
+    triggers: Vec<Duration>,
+    // number of triggers processed before each valid detection and its time
+    detections: Vec<(usize, Duration)>,
+    ready: Vec<Duration>,
+    // every processed reading, during warm-up as well
+    processed: Vec<Duration>,
+    high_readings: u64,
+    // refused by OverflowPolicy::Block, they are not dropped
+    refused_readings: u64,
+    max_pending: u8,
+}
+
+fn run_core(config: DetectorConfig, readings: &[(u64, bool)]) -> (DetectionCore, Run) {
+    let clock = ManualClock::new();
+    let mut core = DetectionCore::new(config);
+    let mut run = Run::default();
+
+    for (after_milisecs, high) in readings.iter() {
+        clock.advance(Duration::from_millis(*after_milisecs));
+        let now = clock.elapsed();
+
+        if *high {
+            run.high_readings += 1;
+            let dropped = core.dropped_readings();
+            if !core.sample(now, true) && core.dropped_readings() == dropped {
+                run.refused_readings += 1;
+            }
+        }
+        run.max_pending = run.max_pending.max(core.pending());
+
+        let reading = core.oldest_pending();
+        let processed = core.pre_detections() + core.warmup_triggers();
+        let pre_detections = core.pre_detections();
+        let last_check = core.last_check();
+
+        let event = core.poll(now);
+
+        // readings discarded at the end of warm-up are not processed
+        if core.last_check() != last_check
+            && core.pre_detections() + core.warmup_triggers() != processed
+        {
+            run.processed.push(now);
+        }
+        if core.pre_detections() != pre_detections {
+            run.triggers.push(reading.unwrap());
+        }
+        match event {
+            Some(CoreEvent::Detection) => run.detections.push((run.triggers.len(), now)),
+            Some(CoreEvent::Ready) => run.ready.push(now),
+            None => {}
+        }
+    }
+
+    (core, run)
+}
+
+// valid detection is possible when triggers of the longest (refresh rate + 2 steps) apart fit
+// within motion time period
+fn continuous_motion() -> impl Strategy<Value = (DetectorConfig, u64)> {
+    (
+        1..=300u64,
+        1..=10i16,
+        0..=200u64,
+        0..=1000u64,
+        1..=MAX_PENDING_CAPACITY,
+        overflow_policy(),
+        1..=20u64,
+    )
+        .prop_map(|(refresh, min, slack, warmup, capacity, policy, step)| {
+            let counted = min as u64;
+            let period = (refresh * counted).max((counted - 1) * (refresh + 2 * step)) + slack;
+            let config = DetectorConfig {
+                refresh_rate: Duration::from_millis(refresh),
+                motion_time_period: Duration::from_millis(period),
+                minimal_triggering_number: min,
+                warmup: Duration::from_millis(warmup),
+                buffer_capacity: capacity,
+                overflow_policy: policy,
+            };
+            (config, step)
+        })
+}
+
+fn sensor_config() -> impl Strategy<Value = SensorConfig> {
+    detector_config().prop_map(|config| SensorConfig {
+        name: String::from("Garage"),
+        pin_number: 0,
+        refresh_rate_milisecs: config.refresh_rate.as_millis() as u64,
+        motion_time_period_milisecs: config.motion_time_period.as_millis() as u64,
+        minimal_triggering_number: config.minimal_triggering_number,
+        warmup_milisecs: config.warmup.as_millis() as u64,
+        buffer_capacity: Some(config.buffer_capacity),
+        overflow_policy: config.overflow_policy,
+        ..Default::default()
+    })
+}
+
+// sensor output changes after 1 - 500 miliseconds
+fn sensor_trace() -> impl Strategy<Value = SensorTrace> {
+    prop::collection::vec((1..=500u64, any::<bool>()), 0..20).prop_map(|changes| {
+        let mut offset = Duration::ZERO;
+        SensorTrace::new(
+            changes
+                .into_iter()
+                .map(|(after_milisecs, high)| {
+                    offset += Duration::from_millis(after_milisecs);
+                    Edge { offset, high }
+                })
+                .collect(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    use pir_motion_sensor::sensor::motion::MotionSensor;
+    use pir_motion_sensor::sensor::trace::ReplayInput;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn detection_needs_enough_triggers_within_motion_period(
+            config in detector_config(),
+            readings in readings(),
+        ) {
+            let (_, run) = run_core(config, &readings);
+            let min = config.minimal_triggering_number as usize;
+
+            let mut previous = 0;
+            for (triggers, _) in run.detections.iter() {
+                prop_assert!(triggers - previous >= min);
+
+                let counted = &run.triggers[triggers - min..*triggers];
+                prop_assert!(counted[min - 1] - counted[0] <= config.motion_time_period);
+                previous = *triggers;
+            }
+        }
+
+        #[test]
+        fn readings_are_processed_in_order_once_per_refresh(
+            config in detector_config(),
+            readings in readings(),
+        ) {
+            let (_, run) = run_core(config, &readings);
+
+            prop_assert!(run.triggers.is_sorted());
+            for processed in run.processed.windows(2) {
+                let since_last = processed[1] - processed[0];
+                prop_assert!(since_last.as_millis() > config.refresh_rate.as_millis());
+            }
+        }
+
+        #[test]
+        fn no_detection_during_warmup(config in detector_config(), readings in readings()) {
+            let (_, run) = run_core(config, &readings);
+
+            if config.warmup.is_zero() {
+                prop_assert!(run.ready.is_empty());
+            } else {
+                prop_assert!(run.ready.len() <= 1);
+                prop_assert!(run.ready.iter().all(|ready| *ready >= config.warmup));
+                prop_assert!(run.ready.len() == 1 || run.detections.is_empty());
+            }
+            prop_assert!(run.detections.iter().all(|(_, at)| *at >= config.warmup));
+            // readings taken during warm-up are never counted, even when processed after it
+            prop_assert!(run.triggers.iter().all(|reading| *reading >= config.warmup));
+        }
+
+        #[test]
+        fn every_high_reading_is_accounted_for(config in detector_config(), readings in readings()) {
+            let (core, run) = run_core(config, &readings);
+
+            prop_assert!(run.max_pending <= core.capacity());
+            prop_assert_eq!(core.pre_detections(), run.triggers.len() as u64);
+            prop_assert_eq!(
+                run.high_readings,
+                core.pre_detections()
+                    + core.warmup_triggers()
+                    + core.pending() as u64
+                    + core.dropped_readings()
+                    + core.coalesced_readings()
+                    + run.refused_readings
+            );
+        }
+
+        #[test]
+        fn continuous_motion_is_detected((config, step) in continuous_motion()) {
+            let refresh = config.refresh_rate.as_millis() as u64;
+            let min = config.minimal_triggering_number as u64;
+            let deadline = config.warmup.as_millis() as u64 + (min + 2) * (refresh + 2 * step);
+
+            let readings = vec![(step, true); (deadline / step) as usize + 1];
+            let (_, run) = run_core(config, &readings);
+
+            prop_assert!(!run.detections.is_empty());
+            prop_assert!(run.detections[0].1 <= Duration::from_millis(deadline));
+        }
+    }
+
+    proptest! {
+        // each case is a few thousands of steps of the sensor
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        //
+        // trigger counter is kept by the caller of process_detections() - it must give exactly
+        // the same valid detections as detection core alone, so properties above hold for
+        // MotionSensor too
+        //
+        #[test]
+        fn motion_sensor_follows_detection_core(
+            config in sensor_config(),
+            trace in sensor_trace(),
+        ) {
+            let milisecs = (trace.duration() + Duration::from_secs(1)).as_millis() as u64;
+
+            let mut core = DetectionCore::new(DetectorConfig::from(&config));
+            let mut expected = Vec::new();
+            for ms in 0..=milisecs {
+                let now = Duration::from_millis(ms);
+                if trace.level_at(now) {
+                    core.sample(now, true);
+                }
+                if core.poll(now) == Some(CoreEvent::Detection) {
+                    expected.push(ms);
+                }
+            }
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let detections = runtime.block_on(async {
+                let clock = ManualClock::starting_at(SystemTime::UNIX_EPOCH);
+                // room for a detection at every step, none of them is dropped
+                let (detections_in, mut detections_out) = mpsc::channel(milisecs as usize + 1);
+
+                let mut sensor = MotionSensor::with_input(
+                    config,
+                    detections_in,
+                    Box::new(ReplayInput::new(trace)),
+                );
+                sensor.set_clock(Arc::new(clock.clone()));
+
+                // the same steps as spawn_detection_threads() does, each one is 1 ms of virtual time
+                let (mut trigger_count, mut last_check_time) = (0, sensor.now());
+                for _ in 0..=milisecs {
+                    sensor.reading_from_sensor().await;
+                    (trigger_count, last_check_time) = sensor
+                        .process_detections(trigger_count, last_check_time)
+                        .await;
+                    clock.advance(Duration::from_millis(1));
+                }
+
+                let mut detections = Vec::new();
+                while let Ok((_, time)) = detections_out.try_recv() {
+                    detections.push(time.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64);
+                }
+                detections
+            });
+
+            prop_assert_eq!(detections, expected);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "std")]
+
+use chrono::{Local, TimeZone};
+use pir_motion_sensor::rules::{RuleAction, RuleConfig, ScheduleConfig, ZoneConfig};
+use std::time::{Duration, SystemTime};
+
+///////////////////////////////////////////////////////////////////////////////
+// Rules: conditions on valid detections of sensors and zones
+///////////////////////////////////////////////////////////////////////////////
+fn zones() -> Vec<ZoneConfig> {
+    vec![ZoneConfig {
+        name: String::from("Entrance"),
+        sensors: vec![String::from("MainDoorSlow"), String::from("Hall")],
+    }]
+}
+
+fn rule(when: &[&str]) -> RuleConfig {
+    RuleConfig {
+        name: String::from("Intruder"),
+        when: when.iter().map(|s| s.to_string()).collect(),
+        within_milisecs: 10000,
+        schedule: None,
+        rate_limit_milisecs: 0,
+        dry_run: false,
+        then: vec![RuleAction::Pulse {
+            pin_number: 21,
+            duration_milisecs: 2000,
+        }],
+    }
+}
+
+// local time of some day
+fn at(hour: u32, minute: u32, second: u32) -> SystemTime {
+    Local
+        .with_ymd_and_hms(2024, 1, 15, hour, minute, second)
+        .unwrap()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::rules::RuleEngine;
+
+    use super::*;
+
+    #[test]
+    fn sensors_detected_within_window() {
+        let mut engine = RuleEngine::new(zones(), vec![rule(&["Garage", "Entrance"])]).unwrap();
+
+        assert!(engine.handle_detection("Garage", at(12, 0, 0)).is_empty());
+        // too late
+        assert!(engine.handle_detection("Hall", at(12, 0, 11)).is_empty());
+        // zone detected again, Garage 20 s ago is too old
+        assert!(
+            engine
+                .handle_detection("MainDoorSlow", at(12, 0, 20))
+                .is_empty()
+        );
+
+        let firings = engine.handle_detection("Garage", at(12, 0, 25));
+        assert_eq!(firings.len(), 1);
+        assert_eq!(firings[0].rule, "Intruder");
+        assert_eq!(firings[0].sensor_name, "Garage");
+        assert!(!firings[0].dry_run);
+
+        // conditions have to be met again for the next firing
+        assert!(engine.handle_detection("Hall", at(12, 0, 26)).is_empty());
+        assert!(engine.handle_detection("Kitchen", at(12, 0, 27)).is_empty());
+    }
+
+    #[test]
+    fn schedule_through_midnight() {
+        let mut night_rule = rule(&["Garage"]);
+        night_rule.schedule = Some(ScheduleConfig {
+            from: String::from("22:00"),
+            to: String::from("06:00"),
+        });
+        let mut engine = RuleEngine::new(zones(), vec![night_rule]).unwrap();
+
+        assert!(engine.handle_detection("Garage", at(21, 59, 59)).is_empty());
+        assert_eq!(engine.handle_detection("Garage", at(22, 0, 0)).len(), 1);
+        assert_eq!(engine.handle_detection("Garage", at(3, 0, 0)).len(), 1);
+        assert!(engine.handle_detection("Garage", at(6, 0, 0)).is_empty());
+        assert!(engine.handle_detection("Garage", at(12, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut limited = rule(&["Garage"]);
+        limited.rate_limit_milisecs = 60000;
+        let mut engine = RuleEngine::new(zones(), vec![limited]).unwrap();
+
+        assert_eq!(engine.handle_detection("Garage", at(12, 0, 0)).len(), 1);
+        assert!(engine.handle_detection("Garage", at(12, 0, 30)).is_empty());
+        assert!(engine.handle_detection("Garage", at(12, 0, 59)).is_empty());
+        assert_eq!(engine.handle_detection("Garage", at(12, 1, 0)).len(), 1);
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut dry = rule(&["Garage"]);
+        dry.dry_run = true;
+        let mut entrance = rule(&["Entrance"]);
+        entrance.name = String::from("Entrance");
+        let mut engine = RuleEngine::new(zones(), vec![dry, entrance]).unwrap();
+
+        let firings = engine.handle_detection("Garage", at(12, 0, 0));
+        assert_eq!(firings.len(), 1);
+        assert!(firings[0].dry_run);
+
+        engine.set_dry_run(true);
+        let firings = engine.handle_detection("Hall", at(12, 0, 0) + Duration::from_secs(1));
+        assert!(firings[0].dry_run);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(RuleEngine::new(zones(), vec![rule(&[])]).is_err());
+        assert!(RuleEngine::new(zones(), vec![rule(&["Garage"]), rule(&["Hall"])]).is_err());
+
+        let mut invalid_schedule = rule(&["Garage"]);
+        invalid_schedule.schedule = Some(ScheduleConfig {
+            from: String::from("25:00"),
+            to: String::from("06:00"),
+        });
+        assert!(RuleEngine::new(zones(), vec![invalid_schedule]).is_err());
+
+        let engine = RuleEngine::new(zones(), vec![rule(&["Garage", "Entrance"])]).unwrap();
+        assert_eq!(engine.sensors(), vec!["Garage", "Hall", "MainDoorSlow"]);
+    }
+}
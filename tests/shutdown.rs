@@ -0,0 +1,147 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::backend::InputBackend;
+use pir_motion_sensor::sensor::trace::ReplayInput;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+///////////////////////////////////////////////////////////////////////////////
+// Graceful shutdown: pending readings are processed and inputs are released
+///////////////////////////////////////////////////////////////////////////////
+// replays the trace and reports when it's dropped, like GPIO pin reset to its original mode
+#[derive(Debug)]
+struct TrackedInput {
+    replay: ReplayInput,
+    released: Arc<AtomicBool>,
+}
+
+impl TrackedInput {
+    fn new(released: &Arc<AtomicBool>) -> Box<Self> {
+        Box::new(Self {
+            // short motion - there are many readings waiting for processing after it
+            replay: ReplayInput::new(common::motion_trace(50)),
+            released: released.clone(),
+        })
+    }
+}
+
+impl InputBackend for TrackedInput {
+    fn is_high(&mut self, now: Instant) -> bool {
+        self.replay.is_high(now)
+    }
+}
+
+impl Drop for TrackedInput {
+    fn drop(&mut self) {
+        self.released.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::events::{DetectionEvent, EventKind};
+    use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
+    use pir_motion_sensor::sensor::manager::SensorManager;
+    use pir_motion_sensor::sensor::motion::MotionSensor;
+    use tokio::sync::{Mutex, mpsc};
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn manager_stop() {
+        let (detections_in, mut detections_out) = mpsc::channel(10);
+        let (events_in, mut events_out) = mpsc::channel::<DetectionEvent>(10);
+        let released = Arc::new(AtomicBool::new(false));
+
+        let mut manager = SensorManager::new();
+        manager.set_detection_channel(detections_in);
+        manager.set_event_channel(events_in);
+        manager
+            .add_sensor_with_input(
+                common::sensor_config("Garage"),
+                TrackedInput::new(&released),
+            )
+            .unwrap();
+
+        // only one reading is processed so far
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(detections_out.try_recv().is_err());
+
+        // the sensor is still used by somebody else
+        let garage = manager.sensor("Garage").unwrap();
+        manager.stop().await;
+
+        assert!(released.load(Ordering::SeqCst));
+        assert_eq!(detections_out.try_recv().unwrap().0, "Garage");
+        assert_eq!(garage.lock().await.stats().valid_detections, 1);
+
+        let mut events = Vec::new();
+        while let Ok(event) = events_out.try_recv() {
+            events.push(event.kind);
+        }
+        assert_eq!(
+            events,
+            vec![
+                EventKind::SensorAdded,
+                EventKind::Detection,
+                EventKind::SensorRemoved
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn detection_threads_shutdown() {
+        let (detections_in, mut detections_out) = mpsc::channel(10);
+        let released = Arc::new(AtomicBool::new(false));
+
+        let sensor = MotionSensor::with_input(
+            common::sensor_config("Garage"),
+            detections_in,
+            TrackedInput::new(&released),
+        );
+        let sensors = Arc::new(vec![Mutex::new(sensor)]);
+
+        let tasks = spawn_detection_threads(sensors.clone(), Arc::new(CancellationToken::new()));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(detections_out.try_recv().is_err());
+
+        tasks.shutdown().await;
+
+        assert!(released.load(Ordering::SeqCst));
+        assert_eq!(detections_out.try_recv().unwrap().0, "Garage");
+
+        // nothing is read after shutdown
+        let readings = sensors[0].lock().await.stats().raw_highs;
+        assert!(!sensors[0].lock().await.reading_from_sensor().await);
+        assert_eq!(sensors[0].lock().await.stats().raw_highs, readings);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_manager() {
+        let released = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(CancellationToken::new());
+
+        let mut manager = SensorManager::with_cancellation_token(stop.clone());
+        manager
+            .add_sensor_with_input(
+                common::sensor_config("Garage"),
+                TrackedInput::new(&released),
+            )
+            .unwrap();
+        let garage = manager.sensor("Garage").unwrap();
+
+        drop(manager);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // the task is finished without stop(), but the token of the caller is not cancelled
+        assert_eq!(Arc::strong_count(&garage), 1);
+        assert!(!stop.is_cancelled());
+        drop(garage);
+        assert!(released.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,163 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::backend::InputBackend;
+use pir_motion_sensor::sensor::simulation::{Noise, Scenario, SimulatedInput, StuckHigh, Walk};
+use std::time::{Duration, Instant};
+
+///////////////////////////////////////////////////////////////////////////////
+// Simulated sensors: walks, random noise and stuck-high outputs from a scenario
+///////////////////////////////////////////////////////////////////////////////
+fn walk_scenario() -> Scenario {
+    Scenario {
+        walks: vec![Walk {
+            sensors: vec![String::from("Hall"), String::from("Kitchen")],
+            start_milisecs: 1000,
+            dwell_milisecs: 500,
+            gap_milisecs: 100,
+            every_milisecs: Some(5000),
+        }],
+        ..Default::default()
+    }
+}
+
+fn noise_scenario(seed: u64) -> Scenario {
+    Scenario {
+        seed,
+        noise: vec![Noise {
+            sensors: Vec::new(),
+            rate_per_minute: 6.0,
+            pulse_milisecs: 200,
+        }],
+        ..Default::default()
+    }
+}
+
+// level of the input every 10 miliseconds for the given time
+fn levels(input: &mut SimulatedInput, start: Instant, milisecs: u64) -> Vec<bool> {
+    (0..milisecs / 10)
+        .map(|n| input.is_high(start + Duration::from_millis(n * 10)))
+        .collect()
+}
+
+fn pulses(levels: &[bool]) -> usize {
+    levels.windows(2).filter(|w| !w[0] && w[1]).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::config::{HealthConfig, SensorConfig};
+    use pir_motion_sensor::sensor::events::{EventKind, FaultKind};
+    use pir_motion_sensor::sensor::manager::SensorManager;
+    use pir_motion_sensor::sensor::simulation::Simulation;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn walk_through_sensors() {
+        let simulation = Simulation::new(walk_scenario());
+        let mut hall = simulation.input("Hall");
+        let mut kitchen = simulation.input("Kitchen");
+        let mut garage = simulation.input("Garage");
+
+        let start = Instant::now();
+        let at = |milisecs: u64| start + Duration::from_millis(milisecs);
+
+        // the first reading of any sensor starts the simulation
+        assert!(!hall.is_high(start));
+
+        assert!(hall.is_high(at(1000)));
+        assert!(hall.is_high(at(1499)));
+        assert!(!hall.is_high(at(1500)));
+
+        // reached after the gap
+        assert!(!kitchen.is_high(at(1599)));
+        assert!(kitchen.is_high(at(1600)));
+        assert!(!kitchen.is_high(at(2100)));
+
+        // and again every 5 seconds
+        assert!(hall.is_high(at(6000)));
+        assert!(kitchen.is_high(at(6600)));
+
+        assert!(!levels(&mut garage, start, 10_000).contains(&true));
+    }
+
+    #[test]
+    fn noise_is_random_but_repeatable() {
+        let start = Instant::now();
+        let hour = 3_600_000;
+
+        let noise = |seed: u64, sensor: &str| {
+            levels(
+                &mut Simulation::new(noise_scenario(seed)).input(sensor),
+                start,
+                hour,
+            )
+        };
+
+        let hall = noise(7, "Hall");
+
+        // 6 pulses per minute on average, 372 in this hour with seed 7
+        assert_eq!(pulses(&hall), 372);
+
+        // the same seed - the same noise, but not the same for other sensors or seeds
+        assert_eq!(hall, noise(7, "Hall"));
+        assert_ne!(hall, noise(7, "Kitchen"));
+        assert_ne!(hall, noise(8, "Hall"));
+    }
+
+    #[test]
+    fn unknown_sensors_in_scenario() {
+        let sensors = vec![String::from("Hall"), String::from("Kitchen")];
+        assert!(walk_scenario().validate(&sensors).is_ok());
+        assert!(walk_scenario().validate(&sensors[..1]).is_err());
+
+        let mut scenario = noise_scenario(7);
+        assert!(scenario.validate(&[]).is_ok());
+        scenario.noise[0].rate_per_minute = f64::NAN;
+        assert!(scenario.validate(&sensors).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stuck_high_sensor_fault() {
+        let (events_in, mut events_out) = mpsc::channel(100);
+        let simulation = Simulation::new(Scenario {
+            stuck_high: vec![StuckHigh {
+                sensor: String::from("Garage"),
+                from_milisecs: 2000,
+                to_milisecs: None,
+            }],
+            ..Default::default()
+        });
+
+        let mut manager = SensorManager::new();
+        manager.set_event_channel(events_in);
+        manager
+            .add_sensor_with_input(
+                SensorConfig {
+                    health: HealthConfig {
+                        stuck_high_milisecs: Some(1000),
+                        no_activity_milisecs: None,
+                        quarantine: true,
+                    },
+                    ..common::sensor_config("Garage")
+                },
+                Box::new(simulation.input("Garage")),
+            )
+            .unwrap();
+
+        let start = tokio::time::Instant::now();
+        let fault = loop {
+            let event = events_out.recv().await.unwrap();
+            if event.kind == EventKind::Fault(FaultKind::StuckHigh) {
+                break start.elapsed();
+            }
+        };
+        // stuck high since 2 seconds, reported a second later
+        assert_eq!(fault, Duration::from_millis(3010));
+
+        manager.stop().await;
+    }
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::config::SensorConfig;
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Statistics of readings and detections per sensor
+///////////////////////////////////////////////////////////////////////////////
+fn sensor_config(warmup_milisecs: u64) -> SensorConfig {
+    SensorConfig {
+        warmup_milisecs,
+        ..common::sensor_config("Garage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::manager::SensorManager;
+    use pir_motion_sensor::sensor::stats::SensorStats;
+    use pir_motion_sensor::sensor::trace::ReplayInput;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    async fn stats(manager: &SensorManager) -> SensorStats {
+        manager.sensor("Garage").unwrap().lock().await.stats()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counters_and_rate() {
+        // nobody reads valid detections
+        let (detections_in, detections_out) = mpsc::channel(10);
+        drop(detections_out);
+
+        // and events
+        let (events_in, events_out) = mpsc::channel(10);
+        drop(events_out);
+
+        let mut manager = SensorManager::new();
+        manager.set_detection_channel(detections_in);
+        manager.set_event_channel(events_in);
+        manager
+            .add_sensor_with_input(
+                sensor_config(0),
+                Box::new(ReplayInput::new(common::motion_trace(700))),
+            )
+            .unwrap();
+
+        assert_eq!(stats(&manager).await, SensorStats::default());
+
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        let after_motion = stats(&manager).await;
+
+        // 70 readings during 700 ms of motion, every third trigger gives a valid detection
+        assert_eq!(after_motion.raw_highs, 70);
+        assert_eq!(after_motion.pre_detections, 16);
+        assert_eq!(after_motion.valid_detections, 5);
+        assert_eq!(after_motion.dropped_readings, 54);
+        assert_eq!(after_motion.dropped_sends, 5);
+        assert_eq!(after_motion.dropped_events, 5);
+        assert_eq!(after_motion.suppressed, 0);
+        assert!(after_motion.first_detection_time <= after_motion.last_detection_time);
+        assert!(after_motion.last_any_detection_time.is_some());
+        assert_eq!(after_motion.detections_last_minute, 5);
+
+        // no motion for more than a minute
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        let later = stats(&manager).await;
+        assert_eq!(later.valid_detections, after_motion.valid_detections);
+        assert_eq!(later.raw_highs, after_motion.raw_highs);
+        assert_eq!(later.detections_last_minute, 0);
+
+        manager.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn suppressed_during_warmup() {
+        let mut manager = SensorManager::new();
+        manager
+            .add_sensor_with_input(
+                sensor_config(1000),
+                Box::new(ReplayInput::new(common::motion_trace(800))),
+            )
+            .unwrap();
+
+        // motion only during warm-up, readings still waiting for processing when it ends are
+        // discarded as well
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        let stats = stats(&manager).await;
+
+        assert_eq!(stats.raw_highs, 80);
+        assert_eq!(stats.suppressed, 17);
+        assert_eq!(stats.pre_detections, 0);
+        assert_eq!(stats.dropped_readings, 63);
+        assert_eq!(stats.valid_detections, 0);
+        assert_eq!(stats.first_detection_time, None);
+
+        manager.stop().await;
+    }
+}
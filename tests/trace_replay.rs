@@ -0,0 +1,155 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Recorded traces are replayed through the same reading/processing logic as
+// real GPIO pins - see sensor::trace module
+///////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::SystemTime};
+
+    use pir_motion_sensor::sensor::clock::ManualClock;
+    use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
+    use pir_motion_sensor::sensor::motion::MotionSensor;
+    use pir_motion_sensor::sensor::trace::{ReplayInput, Trace, TraceRecorder};
+    use tokio::sync::Mutex;
+    use tokio::sync::mpsc::{self, Receiver, Sender};
+    use tokio::time::Instant;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    #[test]
+    fn recorded_trace_can_be_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.csv");
+
+        let recorder = TraceRecorder::create(&path, std::time::Instant::now()).unwrap();
+        let start = recorder.started_at();
+        recorder
+            .record("Garage", start + Duration::from_millis(10), true)
+            .unwrap();
+        recorder
+            .record("Hall, first floor", start + Duration::from_millis(15), true)
+            .unwrap();
+        recorder
+            .record("Garage", start + Duration::from_millis(20), false)
+            .unwrap();
+        recorder.flush().unwrap();
+
+        let trace = Trace::load(&path).unwrap();
+        let garage = trace.sensor("Garage").unwrap();
+        assert_eq!(garage, &common::sensor_trace(&[(10, true), (20, false)]));
+        assert!(!garage.level_at(Duration::from_millis(5)));
+        assert!(garage.level_at(Duration::from_millis(10)));
+        assert!(garage.level_at(Duration::from_millis(19)));
+        assert!(!garage.level_at(Duration::from_millis(25)));
+        assert!(trace.sensor("Hall, first floor").is_some());
+
+        // saving and loading again gives the same trace
+        let copy_path = dir.path().join("copy.csv");
+        trace.save(&copy_path).unwrap();
+        assert_eq!(Trace::load(&copy_path).unwrap(), trace);
+    }
+
+    #[tokio::test]
+    async fn trace_recorded_in_virtual_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.csv");
+
+        let clock = ManualClock::new();
+        let (detections_channel_in, _detections_channel_out) = mpsc::channel(10);
+        let mut sensor = MotionSensor::with_input(
+            common::sensor_config("Motion"),
+            detections_channel_in,
+            Box::new(ReplayInput::new(common::sensor_trace(&[
+                (100, true),
+                (200, false),
+            ]))),
+        );
+        sensor.set_clock(Arc::new(clock.clone()));
+
+        // the clock was created before the recorder, offsets are counted from its time
+        sensor.record_trace(TraceRecorder::create(&path, sensor.now()).unwrap());
+        for _ in 0..30 {
+            sensor.reading_from_sensor().await;
+            clock.advance(Duration::from_millis(10));
+        }
+        drop(sensor);
+
+        let trace = Trace::load(&path).unwrap();
+        assert_eq!(
+            trace.sensor("Motion").unwrap(),
+            &common::sensor_trace(&[(0, false), (100, true), (200, false)])
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replayed_traces_are_processed_like_gpio() {
+        #[allow(clippy::type_complexity)]
+        let (detections_channel_in, mut detections_channel_out): (
+            Sender<(String, SystemTime)>,
+            Receiver<(String, SystemTime)>,
+        ) = mpsc::channel(10);
+
+        // sensor output stays high for 700 ms - two valid detections are expected,
+        // second sensor never goes high
+        let motion = common::sensor_trace(&[(0, true), (700, false)]);
+        let silence = common::sensor_trace(&[(0, false), (700, false)]);
+
+        let sensors = vec![
+            Mutex::new(MotionSensor::with_input(
+                common::sensor_config("Motion"),
+                detections_channel_in.clone(),
+                Box::new(ReplayInput::new(motion)),
+            )),
+            Mutex::new(MotionSensor::with_input(
+                common::sensor_config("Silence"),
+                detections_channel_in,
+                Box::new(ReplayInput::new(silence)),
+            )),
+        ];
+
+        let token = Arc::new(CancellationToken::new());
+        spawn_detection_threads(Arc::new(sensors), token.clone());
+
+        let mut detections = Vec::new();
+        let test_time_start = Instant::now();
+
+        while test_time_start.elapsed() < Duration::from_millis(800) {
+            if let Ok((name, _)) = detections_channel_out.try_recv() {
+                detections.push((test_time_start.elapsed().as_millis() as u64, name));
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        token.cancel();
+
+        assert_eq!(
+            detections,
+            vec![(304, String::from("Motion")), (607, String::from("Motion"))]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reading_returns_sampled_level() {
+        let (detections_channel_in, _detections_channel_out) = mpsc::channel(10);
+        let mut sensor = MotionSensor::with_input(
+            common::sensor_config("Motion"),
+            detections_channel_in,
+            Box::new(ReplayInput::new(common::sensor_trace(&[
+                (100, true),
+                (200, false),
+            ]))),
+        );
+
+        assert!(!sensor.reading_from_sensor().await);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(sensor.reading_from_sensor().await);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!sensor.reading_from_sensor().await);
+    }
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::config::SensorConfig;
+use pir_motion_sensor::sensor::trace::SensorTrace;
+use pir_motion_sensor::sensor::tuning::MotionInterval;
+use std::time::Duration;
+
+///////////////////////////////////////////////////////////////////////////////
+// Offline tuning: trace with one real motion (1-3 s) and two short noise
+// blips afterwards. Noise should be rejected by the recommended settings.
+///////////////////////////////////////////////////////////////////////////////
+fn noisy_trace() -> SensorTrace {
+    common::sensor_trace(&[
+        (1000, true),
+        (3000, false),
+        (5000, true),
+        (5002, false),
+        (8000, true),
+        (8002, false),
+    ])
+}
+
+fn labels() -> Vec<MotionInterval> {
+    vec![MotionInterval {
+        start: Duration::from_millis(1000),
+        end: Duration::from_millis(3000),
+    }]
+}
+
+fn config(minimal_triggering_number: i16) -> SensorConfig {
+    SensorConfig {
+        motion_time_period_milisecs: 1000,
+        minimal_triggering_number,
+        ..common::sensor_config("Garage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::tuning::{TuningRange, evaluate, load_labels, sweep};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn noise_is_reported_as_false_positives() {
+        let sensitive = evaluate(&noisy_trace(), &labels(), &config(1), 1000).await;
+        assert_eq!(sensitive.detected_intervals, 1);
+        assert_eq!(sensitive.false_positives, 4); // two samples of each noise blip
+        assert_eq!(sensitive.valid_detections, 34);
+        assert_eq!(sensitive.true_positives, 30);
+        assert_eq!(sensitive.precision, 30.0 / 34.0);
+
+        let filtered = evaluate(&noisy_trace(), &labels(), &config(5), 1000).await;
+        assert_eq!(filtered.detected_intervals, 1);
+        assert_eq!(filtered.false_positives, 0);
+        assert_eq!(filtered.precision, 1.0);
+        assert_eq!(filtered.recall, 1.0);
+
+        // first valid detection needs 5 refresh periods of motion
+        let latency = filtered.mean_latency.unwrap();
+        assert_eq!(latency, Duration::from_millis(414));
+    }
+
+    #[tokio::test]
+    async fn sweep_recommends_noise_free_settings() {
+        let range = TuningRange {
+            refresh_rate_milisecs: vec![100],
+            motion_time_period_milisecs: vec![1000],
+            minimal_triggering_number: vec![1, 2, 3, 4, 5, 6, 7],
+            tolerance_milisecs: 1000,
+        };
+
+        let results = sweep(&noisy_trace(), &labels(), &config(1), &range).await;
+        assert_eq!(results.len(), 7);
+
+        // from 3 up the noise is rejected (each blip gives two triggers, they are not counted
+        // together with the motion seconds earlier), 3 gives the shortest latency
+        let best = &results[0];
+        assert_eq!(best.config.minimal_triggering_number, 3);
+        assert_eq!(best.false_positives, 0);
+        assert_eq!(best.recall, 1.0);
+        assert!(results[..5].iter().all(|r| r.false_positives == 0));
+        assert!(results[5..].iter().all(|r| r.false_positives > 0));
+    }
+
+    #[test]
+    fn labels_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labels.csv");
+        std::fs::write(&path, "# start,end\n1000,3000\n\n4000, 4500\n").unwrap();
+
+        let labels = load_labels(&path).unwrap();
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[1].end, Duration::from_millis(4500));
+
+        std::fs::write(&path, "3000,1000\n").unwrap();
+        assert!(load_labels(&path).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "tokio")]
+
+use pir_motion_sensor::sensor::motion::MotionSensor;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+///////////////////////////////////////////////////////////////////////////////
+// Detection pipeline driven by virtual time - instead of counting detections
+// within a wall clock timeout, exact valid detections (sensor name and
+// milisecond when they happened) are compared.
+//
+// Sensors read time through sensor::clock::Clock, so the same test can be
+// driven by tokio::time::pause() or by ManualClock.
+///////////////////////////////////////////////////////////////////////////////
+struct TestCase {
+    name: &'static str,
+    refresh_rate_milisecs: u64,
+    motion_time_period_milisecs: u64,
+    minimal_triggering_number: i16,
+    test_detections: Vec<u64>,
+    expected_detections_milisecs: Vec<u64>,
+    test_duration_milisecs: u64,
+}
+
+fn test_cases() -> Vec<TestCase> {
+    vec![
+        TestCase {
+            // two test detections in separate refresh periods, both required for valid detection
+            name: "Simple detection",
+            refresh_rate_milisecs: 500,
+            motion_time_period_milisecs: 1000,
+            minimal_triggering_number: 2,
+            test_detections: vec![490, 990],
+            expected_detections_milisecs: vec![1002],
+            test_duration_milisecs: 1500,
+        },
+        TestCase {
+            // every test detection is a valid one
+            name: "10/10 fast detections",
+            refresh_rate_milisecs: 100,
+            motion_time_period_milisecs: 200,
+            minimal_triggering_number: 1,
+            test_detections: vec![90, 190, 290, 390, 490, 590, 690, 790, 890, 990],
+            expected_detections_milisecs: vec![101, 202, 303, 404, 505, 606, 707, 808, 909, 1010],
+            test_duration_milisecs: 1500,
+        },
+        TestCase {
+            // issue #8 - motion time period is too short to reach minimal triggering number
+            name: "Not enough detections within motion time period",
+            refresh_rate_milisecs: 100,
+            motion_time_period_milisecs: 1000,
+            minimal_triggering_number: 11,
+            test_detections: vec![90, 190, 290, 390, 490, 590, 690, 790, 890, 990],
+            expected_detections_milisecs: vec![],
+            test_duration_milisecs: 1500,
+        },
+    ]
+}
+
+#[allow(clippy::type_complexity)]
+fn sensor_for(
+    test_case: &TestCase,
+) -> (
+    MotionSensor,
+    Sender<(String, SystemTime)>,
+    Receiver<(String, SystemTime)>,
+) {
+    let (detections_channel_in, detections_channel_out) = mpsc::channel(100);
+
+    let sensor = MotionSensor::new(
+        String::from(test_case.name),
+        0,
+        test_case.refresh_rate_milisecs,
+        test_case.motion_time_period_milisecs,
+        test_case.minimal_triggering_number,
+        detections_channel_in.clone(),
+        Some(test_case.test_detections.clone()),
+    );
+
+    (sensor, detections_channel_in, detections_channel_out)
+}
+
+// miliseconds of valid detections since "start"
+fn detections_milisecs(
+    detections: &mut Receiver<(String, SystemTime)>,
+    start: SystemTime,
+) -> Vec<u64> {
+    let mut milisecs = Vec::new();
+    while let Ok((_, time)) = detections.try_recv() {
+        let since_start = time.duration_since(start).unwrap_or(Duration::ZERO);
+        milisecs.push(since_start.as_millis() as u64);
+    }
+    milisecs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use pir_motion_sensor::sensor::clock::{Clock, ManualClock, TokioClock};
+    use pir_motion_sensor::sensor::helpers::spawn_detection_threads;
+    use tokio::sync::Mutex;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_tokio_time() {
+        for test_case in test_cases() {
+            let clock = TokioClock::paused();
+            let start = clock.system_now();
+
+            let (mut sensor, _detections_in, mut detections_out) = sensor_for(&test_case);
+            sensor.set_clock(Arc::new(clock));
+
+            let token = Arc::new(CancellationToken::new());
+            spawn_detection_threads(Arc::new(vec![Mutex::new(sensor)]), token.clone());
+
+            // time is paused - sleep() advances virtual clock as soon as runtime is idle
+            tokio::time::sleep(Duration::from_millis(test_case.test_duration_milisecs)).await;
+            token.cancel();
+
+            assert_eq!(
+                detections_milisecs(&mut detections_out, start),
+                test_case.expected_detections_milisecs,
+                "test case: {}",
+                test_case.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn manual_clock() {
+        for test_case in test_cases() {
+            let clock = ManualClock::new();
+            let start = clock.system_now();
+
+            let (mut sensor, _detections_in, mut detections_out) = sensor_for(&test_case);
+            sensor.set_clock(Arc::new(clock.clone()));
+
+            // the same steps as spawn_detection_threads() does, but each one is 1 ms of virtual time
+            let (mut trigger_count, mut last_check_time) = (0, sensor.now());
+            for _ in 0..=test_case.test_duration_milisecs {
+                sensor.reading_from_sensor().await;
+                (trigger_count, last_check_time) = sensor
+                    .process_detections(trigger_count, last_check_time)
+                    .await;
+                clock.advance(Duration::from_millis(1));
+            }
+
+            assert_eq!(
+                detections_milisecs(&mut detections_out, start),
+                test_case.expected_detections_milisecs,
+                "test case: {}",
+                test_case.name
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wall_clock_follows_system_time() {
+        let clock = TokioClock::new();
+        let paused = TokioClock::paused();
+
+        tokio::time::advance(Duration::from_secs(3600)).await;
+
+        // timestamps of valid detections come from the system clock, so a time step after
+        // startup (NTP sync) is picked up, only paused() follows virtual time
+        let system_now = SystemTime::now();
+        assert!(clock.system_now() >= system_now);
+        assert!(clock.system_now() < system_now + Duration::from_secs(60));
+        assert!(paused.system_now() > system_now + Duration::from_secs(3599));
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use pir_motion_sensor::sensor::config::SensorConfig;
+
+///////////////////////////////////////////////////////////////////////////////
+// Warm-up: triggers right after initialization are never validated
+///////////////////////////////////////////////////////////////////////////////
+fn sensor_config(warmup_milisecs: u64) -> SensorConfig {
+    SensorConfig {
+        warmup_milisecs,
+        ..common::sensor_config("Garage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pir_motion_sensor::sensor::events::EventKind;
+
+    use super::*;
+
+    // sensor output is high for the first 2 seconds
+    async fn replay(config: SensorConfig) -> (Vec<(u64, EventKind)>, u64) {
+        let (events, sensors) = common::replay(config, common::motion_trace(2000), 2000).await;
+        let warmup_triggers = sensors[0].lock().await.warmup_triggers();
+        (events, warmup_triggers)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn triggers_are_not_validated_during_warmup() {
+        let (events, warmup_triggers) = replay(sensor_config(1000)).await;
+
+        // first detection needs "minimal_triggering_number" triggers after warm-up (3 refresh periods)
+        assert_eq!(
+            events,
+            vec![
+                (1001, EventKind::Ready),
+                (1213, EventKind::Detection),
+                (1516, EventKind::Detection),
+                (1819, EventKind::Detection)
+            ]
+        );
+        assert_eq!(warmup_triggers, 19);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_warmup_by_default() {
+        let (events, warmup_triggers) = replay(sensor_config(0)).await;
+
+        // a valid detection every 3 refresh periods
+        let detections = [304, 607, 910, 1213, 1516, 1819];
+        assert_eq!(
+            events,
+            detections.map(|ms| (ms, EventKind::Detection)).to_vec()
+        );
+        assert_eq!(warmup_triggers, 0);
+    }
+}
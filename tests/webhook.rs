@@ -0,0 +1,299 @@
+#![cfg(feature = "webhook")]
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+///////////////////////////////////////////////////////////////////////////////
+// Tests for webhook sink - requests are sent to a local HTTP stub which records
+// path and body of every request and fails first "failures" of them with 503
+// (or responds with any status, see HttpStub::with_responses())
+///////////////////////////////////////////////////////////////////////////////
+#[derive(Clone, Default)]
+struct HttpStub {
+    requests: Arc<Mutex<Vec<(String, String, u16)>>>, // path, body, response status
+}
+
+impl HttpStub {
+    async fn start(listener: TcpListener, failures: usize) -> Self {
+        Self::with_responses(listener, move |_, n| if n < failures { 503 } else { 200 }).await
+    }
+
+    // status of response for the path and number of already received requests to the path
+    async fn with_responses(
+        listener: TcpListener,
+        status: impl Fn(&str, usize) -> u16 + Send + 'static,
+    ) -> Self {
+        let stub = HttpStub::default();
+        let requests = stub.requests.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let Some((path, body)) = read_request(&mut stream).await else {
+                    continue;
+                };
+
+                let received = requests
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(p, _, _)| *p == path)
+                    .count();
+                let status = status(&path, received);
+                requests.lock().unwrap().push((path, body, status));
+
+                let response = format!(
+                    "HTTP/1.1 {status} Stub\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        stub
+    }
+
+    fn requests(&self) -> Vec<(String, String, u16)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn delivered(&self) -> usize {
+        self.requests().iter().filter(|(_, _, s)| *s == 200).count()
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<(String, String)> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    loop {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+
+        let text = String::from_utf8_lossy(&data).to_string();
+        if let Some(headers_end) = text.find("\r\n\r\n") {
+            let headers = &text[..headers_end];
+            let content_length = headers
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().to_string())
+                })
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(0);
+
+            let body = &text[headers_end + 4..];
+            if body.len() >= content_length {
+                let path = headers.split_whitespace().nth(1)?.to_string();
+                return Some((path, body[..content_length].to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+
+    use pir_motion_sensor::sinks::webhook::{RetryConfig, WebhookConfig, WebhookSink};
+    use tokio::sync::mpsc;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    async fn wait_for(condition: impl Fn() -> bool, timeout_milisecs: u64) {
+        let start = Instant::now();
+        while !condition() && (start.elapsed().as_millis() as u64) < timeout_milisecs {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig {
+            initial_backoff_milisecs: 10,
+            max_backoff_milisecs: 40,
+            max_attempts: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_and_routes_detections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        // only requests to /garage fail, the first two of them
+        let stub = HttpStub::with_responses(listener, |path, n| match path {
+            "/garage" if n < 2 => 503,
+            _ => 200,
+        })
+        .await;
+
+        let config = WebhookConfig {
+            urls: vec![format!("{base}/alerts")],
+            routes: HashMap::from([(String::from("Garage"), vec![format!("{base}/garage")])]),
+            payload_template: String::from(r#"{"sensor":"{sensor}","at":{timestamp_ms}}"#),
+            retry: fast_retry(),
+            ..Default::default()
+        };
+
+        let sink = WebhookSink::new(config).unwrap();
+        let (detections_in, detections_out) = mpsc::channel(10);
+        let token = Arc::new(CancellationToken::new());
+        let task = tokio::spawn(sink.run(detections_out, token.clone()));
+
+        let detection_time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        detections_in
+            .send((String::from("Garage"), detection_time))
+            .await
+            .unwrap();
+        detections_in
+            .send((String::from("Kitchen \"1\""), detection_time))
+            .await
+            .unwrap();
+
+        wait_for(|| stub.delivered() == 2, 5000).await;
+        token.cancel();
+        task.await.unwrap();
+
+        // requests to each url, order across urls depends on backoff timing
+        let requests = |path: &str| -> Vec<(String, u16)> {
+            stub.requests()
+                .into_iter()
+                .filter(|(p, _, _)| p == path)
+                .map(|(_, body, status)| (body, status))
+                .collect()
+        };
+
+        // first detection is retried until stub stops failing, the second one is sent elsewhere
+        let garage = String::from(r#"{"sensor":"Garage","at":1700000000123}"#);
+        assert_eq!(
+            requests("/garage"),
+            vec![(garage.clone(), 503), (garage.clone(), 503), (garage, 200)]
+        );
+        assert_eq!(
+            requests("/alerts"),
+            vec![(
+                String::from(r#"{"sensor":"Kitchen \"1\"","at":1700000000123}"#),
+                200
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_are_not_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let stub = HttpStub::with_responses(listener, |path, n| match path {
+            "/gone" => 404,
+            "/busy" if n < 1 => 429,
+            _ => 200,
+        })
+        .await;
+
+        let config = WebhookConfig {
+            urls: vec![format!("{base}/hook")],
+            routes: HashMap::from([
+                (String::from("Garage"), vec![format!("{base}/gone")]),
+                (String::from("Kitchen"), vec![format!("{base}/busy")]),
+            ]),
+            retry: fast_retry(),
+            ..Default::default()
+        };
+
+        let sink = WebhookSink::new(config).unwrap();
+        let (detections_in, detections_out) = mpsc::channel(10);
+        let token = Arc::new(CancellationToken::new());
+        let task = tokio::spawn(sink.run(detections_out, token.clone()));
+
+        for sensor in ["Garage", "Kitchen", "Hall", "Garage"] {
+            detections_in
+                .send((String::from(sensor), SystemTime::now()))
+                .await
+                .unwrap();
+        }
+        drop(detections_in);
+
+        // the sink is finished when there is nothing left to deliver
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let count = |path: &str| stub.requests().iter().filter(|(p, _, _)| p == path).count();
+        // 404 - each detection is sent once, 429 - sent again after backoff
+        assert_eq!(count("/gone"), 2);
+        assert_eq!(count("/busy"), 2);
+        assert_eq!(count("/hook"), 1);
+        assert_eq!(stub.delivered(), 2);
+    }
+
+    #[tokio::test]
+    async fn queue_survives_network_outage() {
+        let queue_dir = tempfile::tempdir().unwrap();
+        let queue_path = queue_dir.path().join("webhook-queue.jsonl");
+
+        // nothing listens on this port yet - "network is down"
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let config = WebhookConfig {
+            urls: vec![format!("http://{addr}/hook")],
+            retry: fast_retry(),
+            queue_path: Some(queue_path.clone()),
+            queue_capacity: 2,
+            ..Default::default()
+        };
+
+        let sink = WebhookSink::new(config.clone()).unwrap();
+        let (detections_in, detections_out) = mpsc::channel(10);
+        let token = Arc::new(CancellationToken::new());
+        let task = tokio::spawn(sink.run(detections_out, token.clone()));
+
+        for sensor in ["First", "Second", "Third"] {
+            detections_in
+                .send((String::from(sensor), SystemTime::now()))
+                .await
+                .unwrap();
+        }
+
+        let queued_lines = || {
+            std::fs::read_to_string(&queue_path)
+                .map(|s| s.lines().count())
+                .unwrap_or(0)
+        };
+        wait_for(|| queued_lines() == 2, 2000).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // stopping the sink while requests are still not delivered
+        token.cancel();
+        task.await.unwrap();
+        assert_eq!(queued_lines(), 2);
+
+        // "network is back" - restarted sink delivers what was left in the queue
+        let stub = HttpStub::start(TcpListener::bind(addr).await.unwrap(), 0).await;
+        let sink = WebhookSink::new(config).unwrap();
+        assert_eq!(sink.pending(), 2);
+
+        let (_detections_in, detections_out) = mpsc::channel(10);
+        let token = Arc::new(CancellationToken::new());
+        let task = tokio::spawn(sink.run(detections_out, token.clone()));
+
+        wait_for(|| stub.delivered() == 2, 5000).await;
+        token.cancel();
+        task.await.unwrap();
+
+        // the oldest detection was pushed out of the full queue
+        let bodies: Vec<String> = stub.requests().into_iter().map(|(_, b, _)| b).collect();
+        assert!(bodies[0].contains("Second"));
+        assert!(bodies[1].contains("Third"));
+        assert_eq!(queued_lines(), 0);
+    }
+}